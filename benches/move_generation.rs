@@ -0,0 +1,47 @@
+//! Move generation speed on positions of random games:
+//! `cargo bench --features bench --bench move_generation`.
+use std::time::Instant;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use checkers_core::game::Game;
+use checkers_core::rules::Variant;
+
+const POSITIONS: usize = 2000;
+const ROUNDS: usize = 50;
+
+fn measure<P>(name: &str, corpus: &mut [P], mut generate: impl FnMut(&mut P) -> usize) {
+    let start = Instant::now();
+    let mut moves = 0;
+    for _ in 0..ROUNDS {
+        moves += corpus.iter_mut().map(&mut generate).sum::<usize>();
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    println!("  {:<28} {:>12.0} moves/s {:>10.0} lists/s",
+             name, moves as f64 / seconds, (ROUNDS * corpus.len()) as f64 / seconds);
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(19);
+    for variant in [Variant::Russian, Variant::International] {
+        let mut corpus = vec![];
+        while corpus.len() < POSITIONS {
+            let mut game = Game::new_standard(variant);
+            for _ in 0..100 {
+                let list = game.current_position.get_move_list(false).list;
+                if list.is_empty() { break; }
+                corpus.push(game.current_position.clone());
+                game.current_position.make_move(&list[rng.gen_range(0..list.len())]);
+            }
+        }
+        println!("{:?}, {} positions", variant, corpus.len());
+        // the generator before get_moves_into, pushing every move under the lock of a shared list
+        measure("cells, Arc<Mutex<MoveList>>", &mut corpus, |pos| pos.get_move_list_locked(false).list.len());
+        measure("cells", &mut corpus, |pos| pos.get_move_list_by_cells(false).list.len());
+        measure("bitboard", &mut corpus, |pos| pos.get_move_list(false).list.len());
+        let mut buffer = vec![];
+        measure("bitboard, caller buffer", &mut corpus, |pos| {
+            pos.get_moves_into(false, &mut buffer);
+            buffer.len()
+        });
+    }
+}
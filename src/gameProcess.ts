@@ -1,5 +1,5 @@
 import * as wasm from "../build-wasm/checkers_core"
-import {Color, Method} from "../build-wasm/checkers_core";
+import {Color, Method, Notation, Variant} from "../build-wasm/checkers_core";
 import {Position} from "./bindings/Position";
 import {ColorType} from "./bindings/ColorType";
 import {MoveList} from "./bindings/MoveList";
@@ -8,6 +8,8 @@ import {Piece} from "./bindings/Piece";
 import {StraightStrike} from "./bindings/StraightStrike";
 import {Strike} from "./bindings/Strike";
 import {BestPos} from "./bindings/BestPos";
+import {SearchLimits} from "./bindings/SearchLimits";
+import {SearchResult} from "./bindings/SearchResult";
 
 
 export type BoardPos = number
@@ -38,6 +40,61 @@ export class GameProcess {
         if (color !== undefined) this.moveColor = color
     }
 
+    static standard(variant: Variant) {
+        const gameProcess = new GameProcess(8)
+        gameProcess.game = wasm.Game.new_standard(variant)
+        return gameProcess
+    }
+
+    get fen(): string {
+        return this.game.fen()
+    }
+
+    set fen(fen: string) {
+        this.game.set_fen_ts_n(fen)
+    }
+
+    get pdn(): string {
+        return this.game.to_pdn()
+    }
+
+    set pdn(pdn: string) {
+        this.game.load_pdn_ts_n(pdn)
+    }
+
+    private resetClicks() {
+        this.moveList = undefined
+        this.strikeChainInd = 0
+        this.moveChainPack = []
+    }
+
+    get ply(): number {
+        return this.game.ply()
+    }
+
+    undo(n: number = 1) {
+        this.resetClicks()
+        return this.game.undo(n)
+    }
+
+    redo(n: number = 1) {
+        this.resetClicks()
+        return this.game.redo(n)
+    }
+
+    gotoPly(ply: number) {
+        this.resetClicks()
+        return this.game.goto_ply(ply)
+    }
+
+    moveByNotation(move: string) {
+        return this.game.make_move_by_notation_ts_n(move)
+    }
+
+    moveListNotation(notation: Notation): string[] {
+        return this.game.get_move_list_notation_ts_n(notation)
+    }
+
     isQuiteMoveList() {
         return this.moveList?.list.length && this.moveList.list[0].mov
     }
@@ -77,28 +134,39 @@ export class GameProcess {
 
     getOrApplyBestMove(apply: boolean) {
         let best = this.game.get_or_apply_best_move(apply) as BestPos
-        if (best.pos?.mov) {
-            let x = best.pos.mov;
-            if (x.mov)
-                x.mov = {
-                    from: this.game.to_board(x.mov.from),
-                    to: this.game.to_board(x.mov.to),
-                    kingMove: x.mov.kingMove
-                }
-        }
-        if (best.pos?.mov?.strike) {
-            best.pos.mov.strike = <Strike>{
-                vec: best.pos.mov.strike.vec.map(it => <StraightStrike>{
+        if (best.pos?.mov) best.pos.mov = this.moveToBoard(best.pos.mov)
+        best.pv = best.pv.map(x => this.moveToBoard(x))
+        return best
+    }
+
+    search(limits: SearchLimits): SearchResult {
+        let result = this.game.search_ts_n(limits) as SearchResult
+        if (result.mov) result.mov = this.moveToBoard(result.mov)
+        result.pv = result.pv.map(x => this.moveToBoard(x))
+        result.lines = result.lines.map(line => ({score: line.score, pv: line.pv.map(x => this.moveToBoard(x))}))
+        return result
+    }
+
+    private moveToBoard(x: MoveItem): MoveItem {
+        if (x.mov)
+            x.mov = {
+                from: this.game.to_board(x.mov.from),
+                to: this.game.to_board(x.mov.to),
+                kingMove: x.mov.kingMove
+            }
+        if (x.strike) {
+            x.strike = <Strike>{
+                vec: x.strike.vec.map(it => <StraightStrike>{
                     kingMove: it.kingMove,
                     from: this.game.to_board(it.from),
                     to: this.game.to_board(it.to),
                     take: this.game.to_board(it.take),
                     v: it.v
                 }),
-                kingMove: best.pos.mov.strike.kingMove
+                kingMove: x.strike.kingMove
             }
         }
-        return best
+        return x
     }
 
     get position(): Position {
@@ -1,6 +1,6 @@
 import {openSync, closeSync, readSync, writeSync, existsSync, mkdirSync, readdirSync} from 'fs'
 
-import {Color} from '../../build-wasm/checkers_core'
+import {Variant} from '../../build-wasm/checkers_core'
 import {GameProcess} from '../gameProcess'
 
 
@@ -438,14 +438,9 @@ function formDataset(datasetItems: DatasetItem[], gamesCount: number, verbose: b
 		type ListOrFinish = Array<number>[] | 'BlackWin' | 'WhiteWin' | `Draw${number}`
 		let listOrFinish: ListOrFinish
 
-		const gameProcess = new GameProcess(8)
+		const gameProcess = GameProcess.standard(Variant.Russian)
 
 		gameProcess.game.set_mcts_lim(200000)
-		const whitePosList = [0, 2, 4, 6, 9, 11, 13, 15, 16, 18, 20, 22]
-		const blackPosList = whitePosList.map(x => 63 - x)
-		whitePosList.forEach(x => gameProcess.insertPiece(x, Color.White, false))
-		blackPosList.forEach(x => gameProcess.insertPiece(x, Color.Black, false))
-		gameProcess.moveColor = Color.White
 
 		let movesCount = 0
 
@@ -753,14 +748,9 @@ function estimatePopulationQuality(population: PopulationItem[], gamesPerPopulat
 			type ListOrFinish = Array<number>[] | 'BlackWin' | 'WhiteWin' | `Draw${number}`
 			let listOrFinish: ListOrFinish
 
-			let gameProcess = new GameProcess(8)
+			let gameProcess = GameProcess.standard(Variant.Russian)
 
 			gameProcess.game.set_depth(1) // <-- depth limit
-			let whitePosList = [0, 2, 4, 6, 9, 11, 13, 15, 16, 18, 20, 22]
-			let blackPosList = whitePosList.map(x => 63 - x)
-			whitePosList.forEach(x => gameProcess.insertPiece(x, Color.White, false))
-			blackPosList.forEach(x => gameProcess.insertPiece(x, Color.Black, false))
-			gameProcess.moveColor = Color.White
 
 			let movesCount = 0
 
@@ -1,4 +1,4 @@
-import {Color} from "../../build-wasm/checkers_core"
+import {Variant} from "../../build-wasm/checkers_core"
 import {GameProcess} from "../gameProcess";
 
 type ListOrFinish = Array<number>[] | 'BlackWin' | 'WhiteWin' | `Draw${number}`
@@ -11,13 +11,8 @@ do {
             gameProcess.game.move_by_tree_index_ts_n(Math.random() * listOrFinish.length >> 0)
         }
     }
-    let gameProcess = new GameProcess(8)
+    let gameProcess = GameProcess.standard(Variant.Russian)
     gameProcess.game.set_mcts_lim(10000) // <-- mcts limit
-    let whitePosList = [0, 2, 4, 6, 9, 11, 13, 15, 16, 18, 20, 22]
-    let blackPosList = whitePosList.map(x => 63 - x)
-    whitePosList.forEach(x => gameProcess.insertPiece(x, Color.White, false))
-    blackPosList.forEach(x => gameProcess.insertPiece(x, Color.Black, false))
-    gameProcess.moveColor = Color.White;
     let movesCount = 0
     let neuralMakeFirstMove = Math.random() < 0.5
     neuralMakeFirstMove = false
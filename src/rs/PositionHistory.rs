@@ -1,4 +1,3 @@
-use std::cell::{RefCell, RefMut};
 use crate::moves_list::MoveItem;
 use crate::position::{Position, PosState};
 use wasm_bindgen::prelude::*;
@@ -6,7 +5,7 @@ use ts_rs::*;
 use serde::Serialize;
 use serde::Deserialize;
 use std::cmp::Ordering;
-use std::rc::Rc;
+use crate::shared::Shared;
 use crate::color::Color::{Black, White};
 use crate::log;
 use crate::piece::Piece;
@@ -48,7 +47,7 @@ impl PositionAndMove {
 }
 #[derive(Debug)]
 pub struct PositionHistory {
-    pub list: Vec<Rc<RefCell<PositionAndMove>>>,
+    pub list: Vec<Shared<PositionAndMove>>,
 }
 
 impl PositionHistory {
@@ -64,7 +63,7 @@ impl PositionHistory {
             list: vec![]
         }
     }
-    pub fn last(&mut self) -> Rc<RefCell<PositionAndMove>> {
+    pub fn last(&mut self) -> Shared<PositionAndMove> {
         self.list.last().unwrap().clone()
     }
 
@@ -73,32 +72,140 @@ impl PositionHistory {
         self.list = self.list[0..to].to_owned();
     }
     pub fn push(&mut self, pos_mov: PositionAndMove) -> Option<FinishType> {
-        self.list.push(Rc::new(RefCell::from(pos_mov)));
+        self.list.push(Shared::new(pos_mov));
         self.finish_check()
     }
 
-    pub fn push_rc(&mut self, pos_mov: Rc<RefCell<PositionAndMove>>) -> Option<FinishType> {
+    pub fn push_rc(&mut self, pos_mov: Shared<PositionAndMove>) -> Option<FinishType> {
         self.list.push(pos_mov);
         self.finish_check()
     }
 
-    pub fn pop(&mut self) -> Option<Rc<RefCell<PositionAndMove>>> {
+    pub fn pop(&mut self) -> Option<Shared<PositionAndMove>> {
         self.list.pop()
     }
 
     pub fn finish_check(&mut self) -> Option<FinishType> {
-        let mut i = self.list.len();
+        let i = self.list.len();
         if i == 0 { return None; }
         let pos_history = &self.list;
         let ref mut current = &pos_history[i - 1];
         let list = current.borrow_mut().pos.get_move_list_cached();
         // log(&format!(" {:?} ", list.as_ref().as_ref().unwrap().list.len()));
+        let rules = current.borrow().pos.environment.rules.clone();
         if list.as_ref().as_ref().unwrap().list.len() == 0 {
-            return if current.borrow().pos.next_move.is_some() &&
-                current.borrow().pos.next_move.unwrap() == White { Some(BlackWin) } else { Some(WhiteWin) };
+            // the side without moves loses, in antidraughts it wins
+            let white_stuck = current.borrow().pos.next_move.is_some() &&
+                current.borrow().pos.next_move.unwrap() == White;
+            return if white_stuck != rules.losing() { Some(BlackWin) } else { Some(WhiteWin) };
+        }
+        rules.draw_check(self)
+    }
+
+    /// Number of the last moves made by kings without captures.
+    pub fn king_quiet_run(&self) -> usize {
+        self.list.iter().rev().take_while(|x| {
+            let x = x.borrow();
+            match &x.mov {
+                Some(mov) => mov.mov.is_some() && !mov.is_king_move() &&
+                    x.move_piece().as_ref().is_some_and(|piece| piece.is_king),
+                None => false
+            }
+        }).count()
+    }
+
+    /// The draw rules may count the moves made before the current position, which its hash
+    /// does not cover: kings have moved since the last capture or man move, or both sides have kings.
+    pub fn draw_counts_history(&self) -> bool {
+        let Some(last) = self.list.last() else { return false };
+        let state = &last.borrow().pos.state;
+        (state.white.king > 0 && state.black.king > 0) || self.king_quiet_run() > 0
+    }
+
+    /// Number of the last positions with the same material as the current one.
+    pub fn material_run(&self) -> usize {
+        let current = self.list.last().unwrap().borrow().pos.state.clone();
+        self.list.iter().rev().take_while(|x| x.borrow().pos.state == current).count()
+    }
+
+    /// How many times the current position (with the same side to move) occurred since
+    /// the last capture or man move, the current one included.
+    pub fn repetitions(&self) -> usize {
+        let n = self.list.len();
+        let run = self.king_quiet_run();
+        let current = self.list[n - 1].borrow();
+        let mut k = 2;
+        let mut repeats = 1;
+        while k <= run && k < n {
+            if self.list[n - 1 - k].borrow().pos.hash() == current.pos.hash() { repeats += 1; }
+            k += 2;
+        }
+        repeats
+    }
+
+    /// Endings against a lone king: the stronger side with three pieces (a king among them)
+    /// has `three_plies` to win, with two or one piece `two_plies`.
+    fn lone_king_draw(&self, three_plies: usize, two_plies: Option<usize>) -> Option<FinishType> {
+        let mut state = self.list.last().unwrap().borrow().pos.state.clone();
+        for color in [White, Black] {
+            let lone = state.get_count(color).clone();
+            let strong = state.get_count(!color).clone();
+            if lone.king == 1 && lone.simple == 0 && strong.king > 0 {
+                let run = self.material_run();
+                let total = strong.king + strong.simple;
+                if total == 3 && run >= three_plies { return Some(Draw3); }
+                if total < 3 && two_plies.is_some_and(|plies| run >= plies) { return Some(Draw4); }
+            }
         }
+        None
+    }
+
+    pub(crate) fn fmjd_draw_check(&mut self) -> Option<FinishType> {
+        // the same position for the third time with the same side to move
+        if self.repetitions() >= 3 { return Some(Draw2); }
+        // 25 moves of each player made only by kings, without captures
+        if self.king_quiet_run() >= 50 { return Some(Draw1); }
+        // 16 moves against a lone king with three pieces, 5 moves with two or one
+        self.lone_king_draw(32, Some(10))
+    }
+
+    pub(crate) fn brazilian_draw_check(&mut self) -> Option<FinishType> {
+        if self.repetitions() >= 3 { return Some(Draw2); }
+        // 20 moves of each player made only by kings, without captures
+        if self.king_quiet_run() >= 40 { return Some(Draw1); }
+        self.lone_king_draw(32, Some(10))
+    }
+
+    pub(crate) fn pool_draw_check(&mut self) -> Option<FinishType> {
+        if self.repetitions() >= 3 { return Some(Draw2); }
+        // 40 moves of each player without a capture or a man move
+        if self.king_quiet_run() >= 80 { return Some(Draw1); }
+        // three pieces have 13 moves to beat a lone king
+        self.lone_king_draw(26, None)
+    }
+
+    pub(crate) fn english_draw_check(&mut self) -> Option<FinishType> {
+        if self.repetitions() >= 3 { return Some(Draw2); }
+        // 40 moves of each player without a capture or a man move
+        if self.king_quiet_run() >= 80 { return Some(Draw1); }
+        None
+    }
+
+    pub(crate) fn frisian_draw_check(&mut self) -> Option<FinishType> {
+        if self.repetitions() >= 3 { return Some(Draw2); }
+        // two pieces or one have 7 moves to beat a lone king
+        self.lone_king_draw(usize::MAX, Some(14))
+    }
 
+    pub(crate) fn repetition_draw_check(&mut self) -> Option<FinishType> {
+        if self.repetitions() >= 3 { return Some(Draw2); }
+        None
+    }
 
+    pub(crate) fn russian_draw_check(&mut self) -> Option<FinishType> {
+        let mut i = self.list.len();
+        let pos_history = &self.list;
+        let current = &pos_history[i - 1];
         let environment = current.borrow().pos.environment.clone();
         if current.borrow_mut().pos.state.get_count(White).king > 0 &&
             current.borrow_mut().pos.state.get_count(Black).king > 0 {
@@ -146,7 +253,7 @@ impl PositionHistory {
             current.borrow_mut().pos.state.repeats = 0;
             let mut j = i - 1;
             while pos_history[j].borrow().pos.state == current.borrow().pos.state {
-                if current.borrow().pos == pos_history[j].borrow().pos {
+                if current.borrow().pos.hash() == pos_history[j].borrow().pos.hash() {
                     current.borrow_mut().pos.state.repeats += 1;
                     if current.borrow().pos.state.repeats > 2 {
                         return Some(Draw2);
@@ -217,10 +324,15 @@ impl PositionHistory {
 #[ts(export)]
 #[derive(Serialize, Debug, Clone, Deserialize)]
 pub enum FinishType {
+    /// only kings moved, without captures, for too long
     Draw1,
+    /// the same position repeated three times
     Draw2,
+    /// three pieces could not beat a lone king in time
     Draw3,
+    /// the material did not change for too long
     Draw4,
+    /// a lone king on the main road held out
     Draw5,
     BlackWin,
     WhiteWin,
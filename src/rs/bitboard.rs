@@ -0,0 +1,481 @@
+use std::mem::take;
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+use std::sync::Arc;
+use crate::color::Color;
+use crate::moves::{BoardPos, QuietMove, StraightStrike};
+use crate::moves_list::{MoveItem, Strike};
+use crate::piece::Piece;
+use crate::position::Position;
+use crate::position_environment::PositionEnvironment;
+use crate::rules::Promotion;
+use crate::vector::Vector;
+
+/// Set of packed squares, a bit per square.
+pub trait Bits: Copy + Eq + Default
++ BitAnd<Output=Self> + BitOr<Output=Self> + BitXor<Output=Self> + Not<Output=Self> {
+    /// Squares the set can hold.
+    const SQUARES: usize;
+
+    fn square(pos: BoardPos) -> Self;
+
+    fn count(self) -> u32;
+
+    /// Lowest square of a non-empty set.
+    fn first(self) -> BoardPos;
+
+    /// The squares of a `u128` set that fit.
+    fn from_u128(bits: u128) -> Self;
+
+    /// Moves every square by `by`, up if positive.
+    fn shift(self, by: i32) -> Self;
+
+    fn is_empty(self) -> bool {
+        self == Self::default()
+    }
+
+    fn has(self, pos: BoardPos) -> bool {
+        !(self & Self::square(pos)).is_empty()
+    }
+
+    /// Squares in ascending order.
+    fn squares(self) -> Squares<Self> {
+        Squares(self)
+    }
+}
+
+macro_rules! impl_bits {
+    ($($t:ty),*) => {$(
+        impl Bits for $t {
+            const SQUARES: usize = <$t>::BITS as usize;
+
+            fn square(pos: BoardPos) -> Self { 1 << pos }
+
+            fn count(self) -> u32 { self.count_ones() }
+
+            fn first(self) -> BoardPos { self.trailing_zeros() as BoardPos }
+
+            fn from_u128(bits: u128) -> Self { bits as $t }
+
+            fn shift(self, by: i32) -> Self { if by >= 0 { self << by } else { self >> -by } }
+        }
+    )*};
+}
+
+impl_bits!(u32, u64, u128);
+
+pub struct Squares<B: Bits>(B);
+
+impl<B: Bits> Iterator for Squares<B> {
+    type Item = BoardPos;
+
+    fn next(&mut self) -> Option<BoardPos> {
+        if self.0.is_empty() { return None; }
+        let pos = self.0.first();
+        self.0 = self.0 ^ B::square(pos);
+        Some(pos)
+    }
+}
+
+/// Pieces of a position as sets of packed squares: u32 holds the 8x8 board,
+/// u64 the 10x10 and Turkish boards, u128 the 12x12 one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BitBoard<B: Bits> {
+    pub white: B,
+    pub black: B,
+    pub kings: B,
+}
+
+impl<B: Bits> BitBoard<B> {
+    pub fn from_cells(cells: &[Option<Piece>]) -> BitBoard<B> {
+        assert!(cells.len() <= B::SQUARES, "{} squares don't fit the bitboard", cells.len());
+        let mut board = BitBoard::default();
+        for piece in cells.iter().flatten() {
+            board.put(piece.pos, piece.color, piece.is_king);
+        }
+        board
+    }
+
+    pub fn pieces(&self, color: Color) -> B {
+        if color == Color::White { self.white } else { self.black }
+    }
+
+    pub fn occupied(&self) -> B {
+        self.white | self.black
+    }
+
+    pub fn color_at(&self, pos: BoardPos) -> Option<Color> {
+        if self.white.has(pos) {
+            Some(Color::White)
+        } else if self.black.has(pos) { Some(Color::Black) } else { None }
+    }
+
+    pub fn piece_at(&self, pos: BoardPos) -> Option<Piece> {
+        self.color_at(pos).map(|color| Piece::new(pos, color, self.kings.has(pos)))
+    }
+
+    fn put(&mut self, pos: BoardPos, color: Color, is_king: bool) {
+        let square = B::square(pos);
+        if color == Color::White { self.white = self.white | square; } else { self.black = self.black | square; }
+        if is_king { self.kings = self.kings | square; }
+    }
+
+    fn clear(&mut self, pos: BoardPos) {
+        let rest = !B::square(pos);
+        self.white = self.white & rest;
+        self.black = self.black & rest;
+        self.kings = self.kings & rest;
+    }
+
+    /// Puts the piece on the empty square or takes it from there.
+    pub fn toggle(&mut self, pos: BoardPos, color: Color, is_king: bool) {
+        let square = B::square(pos);
+        if color == Color::White { self.white = self.white ^ square; } else { self.black = self.black ^ square; }
+        if is_king { self.kings = self.kings ^ square; }
+    }
+}
+
+impl BitBoard<u128> {
+    /// The same pieces on a smaller set, the board has to fit it.
+    pub fn cast<B: Bits>(&self) -> BitBoard<B> {
+        BitBoard { white: B::from_u128(self.white), black: B::from_u128(self.black), kings: B::from_u128(self.kings) }
+    }
+}
+
+/// A step in each direction of `Vector` as shifts of square sets: the squares of a group's mask
+/// move by the group's shift. Rows of the dark squares alternate, so a diagonal needs two groups.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Shifts([[(i32, u128); 2]; 8]);
+
+impl Shifts {
+    pub fn from_vectors(vectors_map: &[Vec<Arc<Vector<BoardPos>>>]) -> Shifts {
+        let mut shifts = Shifts::default();
+        for (pos, vectors) in vectors_map.iter().enumerate() {
+            for v in vectors {
+                let by = v.points[1] as i32 - pos as i32;
+                let groups = &mut shifts.0[v.direction as usize];
+                let group = groups.iter().position(|(shift, mask)| *mask == 0 || *shift == by)
+                    .expect("more than two shifts in a direction");
+                groups[group] = (by, groups[group].1 | 1 << pos);
+            }
+        }
+        shifts
+    }
+
+    fn cast<B: Bits>(&self) -> [[(i32, B); 2]; 8] {
+        self.0.map(|groups| groups.map(|(by, mask)| (by, B::from_u128(mask))))
+    }
+}
+
+fn opposite(direction: i8) -> i8 {
+    direction / 4 * 4 + (direction + 2) % 4
+}
+
+// one capture of a chain, the piece may land on any of the squares behind the taken one
+#[derive(Clone, Copy)]
+struct Jump<B: Bits> {
+    direction: i8,
+    from: BoardPos,
+    take: BoardPos,
+    to: BoardPos,
+    landings: B,
+    king_move: bool,
+}
+
+// capture search state: the moving piece goes over the board, taken pieces stay on it
+// as stricken until the move ends, unless the rules remove them at once
+struct Generator<'a, B: Bits> {
+    environment: &'a PositionEnvironment,
+    shifts: [[(i32, B); 2]; 8],
+    board: BitBoard<B>,
+    stricken: B,
+    promotion: Promotion,
+    removes_at_once: bool,
+    flying_kings: bool,
+    for_front: bool,
+    // the captures made so far by the moving piece
+    chain: Vec<Jump<B>>,
+    list: Vec<MoveItem>,
+}
+
+impl<'a, B: Bits> Generator<'a, B> {
+    // the squares one step away in the direction
+    fn step(&self, direction: i8, squares: B) -> B {
+        let [(a, a_mask), (b, b_mask)] = self.shifts[direction as usize];
+        (squares & a_mask).shift(a) | (squares & b_mask).shift(b)
+    }
+
+    fn jump(&self, piece: &Piece, from: BoardPos, direction: i8) -> Option<Jump<B>> {
+        let occupied = self.board.occupied();
+        let flying = piece.is_king && self.flying_kings;
+        let mut take = self.step(direction, B::square(from));
+        while flying && !take.is_empty() && (take & occupied).is_empty() {
+            take = self.step(direction, take);
+        }
+        // the first piece on the line, not taken yet, with the landing square right behind it
+        if (take & occupied).is_empty() || !(take & (self.board.pieces(piece.color) | self.stricken)).is_empty() {
+            return None;
+        }
+        let mut landing = self.step(direction, take);
+        if landing.is_empty() || !(landing & occupied).is_empty() { return None; }
+        let take = take.first();
+        if !self.environment.rules.can_take(piece, &self.board.piece_at(take).unwrap()) { return None; }
+        let to = landing.first();
+        let mut landings = landing;
+        // a flying king may stop on any free square behind the taken piece
+        while flying && !landing.is_empty() {
+            landing = self.step(direction, landing) & !occupied;
+            landings = landings | landing;
+        }
+        Some(Jump { direction, from, take, to, landings, king_move: self.environment.is_king_move_for(piece, to) })
+    }
+
+    // the move made of the chain and the last capture
+    fn strike(&self, last: &Jump<B>) -> Strike {
+        let vec = self.chain.iter().chain([last]).map(|jump| {
+            let mut v = vec![];
+            let mut landing = self.step(jump.direction, B::square(jump.take));
+            while !(landing & jump.landings).is_empty() {
+                v.push(landing.first());
+                landing = self.step(jump.direction, landing);
+            }
+            StraightStrike { v, from: jump.from, take: jump.take, to: jump.to, king_move: jump.king_move }
+        }).collect();
+        Strike { vec, king_move: self.chain.iter().chain([last]).any(|jump| jump.king_move) }
+    }
+
+    // the same search as Position::get_strike_list, so the moves come in the same order
+    fn strikes(&mut self, pos: BoardPos, banned: u8) -> bool {
+        let environment = self.environment;
+        let piece = self.board.piece_at(pos).unwrap();
+        let directions = environment.rules.directions(&piece, true);
+        let mut success_call = false;
+        for &direction in environment.rules.board_directions() {
+            if !directions.contains(&direction) || banned & 1 << direction != 0 { continue; }
+            let Some(jump) = self.jump(&piece, pos, direction) else { continue };
+            success_call = true;
+            let mut banned = 1 << opposite(direction);
+            let mut recurrent_chain = false;
+            let mut strike_move = jump;
+            if self.promotion == Promotion::EndOfMove { strike_move.king_move = false; }
+            let before_strike = (self.board, self.stricken);
+            if self.removes_at_once {
+                self.board.clear(jump.take);
+            } else {
+                self.stricken = self.stricken | B::square(jump.take);
+            }
+            let mut landing = B::square(jump.to);
+            while !(landing & jump.landings).is_empty() {
+                strike_move.to = landing.first();
+                let before_move = self.board;
+                self.board.clear(pos);
+                self.board.put(strike_move.to, piece.color, piece.is_king || strike_move.king_move);
+                self.chain.push(strike_move);
+                let crowned_stop = strike_move.king_move && self.promotion == Promotion::StopsStrike;
+                if !crowned_stop && self.strikes(strike_move.to, banned) {
+                    recurrent_chain = true;
+                }
+                self.chain.pop();
+                self.board = before_move;
+                if !self.for_front && banned.count_ones() < 2 {
+                    banned |= 1 << direction;
+                }
+                landing = self.step(direction, landing);
+            }
+            (self.board, self.stricken) = before_strike;
+            if !recurrent_chain {
+                let mut landing = B::square(jump.to);
+                while !(landing & jump.landings).is_empty() {
+                    let mut last = jump;
+                    last.to = landing.first();
+                    if self.promotion == Promotion::EndOfMove {
+                        last.king_move = environment.is_king_move_for(&piece, last.to);
+                    }
+                    let strike = self.strike(&last);
+                    self.list.push(MoveItem { strike: Some(strike), mov: None });
+                    landing = self.step(direction, landing);
+                }
+            }
+        }
+        success_call
+    }
+
+    // the pieces with a free square next to them, in each direction
+    fn movers(&self, pieces: B, empty: B) -> [B; 8] {
+        let mut movers = [B::default(); 8];
+        for &direction in self.environment.rules.board_directions() {
+            movers[direction as usize] = pieces & self.step(opposite(direction), empty);
+        }
+        movers
+    }
+
+    fn quiet_moves(&mut self, color: Color) {
+        let environment = self.environment;
+        let rules = environment.rules.as_ref();
+        let empty = !self.board.occupied();
+        let movers = self.movers(self.board.pieces(color), empty);
+        let man_directions = rules.directions(&Piece::new(0, color, false), false);
+        let king_directions = rules.directions(&Piece::new(0, color, true), false);
+        for pos in movers.iter().fold(B::default(), |all, x| all | *x).squares() {
+            let piece = Piece::new(pos, color, self.board.kings.has(pos));
+            let directions = if piece.is_king { king_directions } else { man_directions };
+            let flying = piece.is_king && self.flying_kings;
+            for &direction in rules.board_directions() {
+                if !directions.contains(&direction) || !movers[direction as usize].has(pos) { continue; }
+                let mut to = self.step(direction, B::square(pos));
+                while !(to & empty).is_empty() {
+                    self.list.push(MoveItem {
+                        mov: Some(QuietMove {
+                            from: pos,
+                            to: to.first(),
+                            king_move: environment.is_king_move_for(&piece, to.first()),
+                        }),
+                        strike: None,
+                    });
+                    if !flying { break; }
+                    to = self.step(direction, to);
+                }
+            }
+        }
+    }
+
+    // the men next to an enemy piece with a free square behind it, and all the kings
+    fn may_strike(&self, color: Color) -> B {
+        let own = self.board.pieces(color);
+        let men = own & !self.board.kings;
+        let enemy = self.board.pieces(!color);
+        let empty = !self.board.occupied();
+        let directions = self.environment.rules.directions(&Piece::new(0, color, false), true);
+        directions.iter().fold(own & self.board.kings, |may, &direction| {
+            let back = opposite(direction);
+            may | men & self.step(back, enemy & self.step(back, empty))
+        })
+    }
+}
+
+fn generate_on<B: Bits>(pos: &Position, for_front: bool, list: &mut Vec<MoveItem>) {
+    let color = pos.next_move.unwrap_or_else(|| panic!("Color of next move undefined!"));
+    let environment = pos.environment.as_ref();
+    let rules = environment.rules.as_ref();
+    let mut generator = Generator {
+        environment,
+        shifts: environment.shifts.cast(),
+        board: pos.board.cast(),
+        stricken: B::default(),
+        promotion: rules.promotion(),
+        removes_at_once: rules.removes_at_once(),
+        flying_kings: rules.flying_kings(),
+        for_front,
+        chain: vec![],
+        list: take(list),
+    };
+    generator.list.clear();
+    for piece in generator.may_strike(color).squares() {
+        generator.strikes(piece, 0);
+    }
+    if generator.list.is_empty() {
+        generator.quiet_moves(color);
+        rules.filter_moves(pos, &mut generator.list);
+    } else {
+        rules.filter_strikes(pos, &mut generator.list);
+    }
+    *list = generator.list;
+}
+
+/// Moves of the side to move into `list`, the same list `Position::get_move_list_by_cells` makes,
+/// on the smallest bitboard the board fits in.
+pub fn generate(pos: &Position, for_front: bool, list: &mut Vec<MoveItem>) {
+    match pos.cells.len() {
+        0..=32 => generate_on::<u32>(pos, for_front, list),
+        33..=64 => generate_on::<u64>(pos, for_front, list),
+        _ => generate_on::<u128>(pos, for_front, list),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use crate::bitboard::{BitBoard, Bits};
+    use crate::color::Color;
+    use crate::game::Game;
+    use crate::moves_list::MoveItem;
+    use crate::piece::Piece;
+    use crate::position::Position;
+    use crate::rules::Variant;
+
+    const VARIANTS: [Variant; 10] = [Variant::Russian, Variant::Pool, Variant::Brazilian, Variant::English,
+        Variant::Italian, Variant::Turkish, Variant::Antidraughts, Variant::International, Variant::Frisian,
+        Variant::Canadian];
+
+    fn same_lists(pos: &mut Position, buffer: &mut Vec<MoveItem>) {
+        // the board kept by the moves
+        assert_eq!(pos.board, BitBoard::from_cells(&pos.cells), "{}", pos.to_fen());
+        for for_front in [false, true] {
+            let list = pos.get_move_list(for_front).list;
+            assert_eq!(list, pos.get_move_list_by_cells(for_front).list, "{}", pos.to_fen());
+            // the buffer still holds the moves of another position
+            pos.get_moves_into(for_front, buffer);
+            assert_eq!(*buffer, list);
+        }
+    }
+
+    #[test]
+    fn board_sets() {
+        let pos = Game::new_standard(Variant::Russian).current_position;
+        let board = BitBoard::<u32>::from_cells(&pos.cells);
+        assert_eq!((board.white.count(), board.black.count(), board.kings), (12, 12, 0));
+        assert_eq!(board.white.squares().collect::<Vec<_>>(), (0..12).collect::<Vec<_>>());
+        assert_eq!(board.piece_at(31), Some(Piece::new(31, Color::Black, false)));
+        assert_eq!(board.piece_at(15), None);
+        let pos = Game::new_standard(Variant::Canadian).current_position;
+        let board = BitBoard::<u128>::from_cells(&pos.cells);
+        assert_eq!(board.occupied().count(), 60);
+        assert!(board.black.has(71));
+    }
+
+    #[test]
+    fn played_positions() {
+        let mut rng = StdRng::seed_from_u64(17);
+        let mut buffer = vec![];
+        for variant in VARIANTS {
+            for _ in 0..20 {
+                let mut pos = Game::new_standard(variant).current_position;
+                for _ in 0..200 {
+                    same_lists(&mut pos, &mut buffer);
+                    let list = pos.get_move_list(false).list;
+                    if list.is_empty() { break; }
+                    pos.make_move(&list[rng.gen_range(0..list.len())]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_positions() {
+        let mut rng = StdRng::seed_from_u64(71);
+        let mut buffer = vec![];
+        for variant in VARIANTS {
+            let environment = Game::new_variant(variant).current_position.environment;
+            for _ in 0..1000 {
+                let mut pos = Position::new(environment.clone());
+                pos.next_move = Some(if rng.gen_bool(0.5) { Color::White } else { Color::Black });
+                for square in 0..environment.squares() {
+                    if rng.gen_bool(0.6) { continue; }
+                    let color = if rng.gen_bool(0.5) { Color::White } else { Color::Black };
+                    pos.insert_piece(Piece::new(square, color, rng.gen_bool(0.3)));
+                }
+                same_lists(&mut pos, &mut buffer);
+            }
+        }
+    }
+
+    #[test]
+    fn readme_king_strike() {
+        for variant in [Variant::Russian, Variant::Pool] {
+            let mut game = Game::new_variant(variant);
+            game.set_fen("W:WK29:B9,10,11,17,18,19,25,26,27").unwrap();
+            same_lists(&mut game.current_position, &mut vec![]);
+            assert_eq!(game.current_position.get_move_list(false).list.len(), 42);
+        }
+    }
+}
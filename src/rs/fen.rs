@@ -0,0 +1,181 @@
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use crate::color::Color;
+use crate::piece::Piece;
+use crate::position::Position;
+use crate::position_environment::PositionEnvironment;
+
+/// Why a FEN string (`W:W21,22,K5:B1,2,3`) could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// no `:` between the side to move and the piece lists
+    Format(String),
+    /// the side to move is not `W` or `B`
+    Color(String),
+    /// a piece list doesn't start with `W` or `B`
+    PieceList(String),
+    /// a square is not a number or a range like `1-12`
+    Square(String),
+    /// the square number is outside of 1..=squares of the board
+    OutOfBoard(usize, usize),
+    /// the same square is given twice
+    Occupied(usize),
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::Format(fen) =>
+                write!(f, "FEN \"{}\" must look like W:W21,22,K5:B1,2,3", fen),
+            FenError::Color(color) =>
+                write!(f, "side to move must be W or B, found \"{}\"", color),
+            FenError::PieceList(list) =>
+                write!(f, "piece list \"{}\" must start with W or B", list),
+            FenError::Square(square) =>
+                write!(f, "\"{}\" is not a square number or a range of squares", square),
+            FenError::OutOfBoard(square, squares) =>
+                write!(f, "square {} is out of the board, squares are 1-{}", square, squares),
+            FenError::Occupied(square) =>
+                write!(f, "square {} is given twice", square),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+fn color(token: &str) -> Option<Color> {
+    match token {
+        "W" => Some(Color::White),
+        "B" => Some(Color::Black),
+        _ => None,
+    }
+}
+
+fn square(token: &str) -> Result<usize, FenError> {
+    token.trim().parse::<usize>().map_err(|_| FenError::Square(token.to_string()))
+}
+
+impl Position {
+    /// Reads a FEN string with the numeric squares of the environment's board.
+    /// Square ranges (`W:W31-50:B1-20`) and a closing dot are accepted.
+    pub fn from_fen(environment: Arc<PositionEnvironment>, fen: &str) -> Result<Position, FenError> {
+        let text = fen.trim().trim_end_matches('.');
+        let mut sections = text.split(':');
+        let next_move = sections.next().map(str::trim).unwrap_or("");
+        let next_move = color(next_move).ok_or_else(|| FenError::Color(next_move.to_string()))?;
+        let mut pos = Position::new(environment.clone());
+        pos.next_move = Some(next_move);
+        let mut lists = 0;
+        for section in sections {
+            let section = section.trim();
+            let list_color = color(section.get(..1).unwrap_or(""))
+                .ok_or_else(|| FenError::PieceList(section.to_string()))?;
+            lists += 1;
+            for item in section[1..].split(',').map(str::trim).filter(|x| !x.is_empty()) {
+                let (is_king, squares) = match item.strip_prefix('K') {
+                    Some(squares) => (true, squares),
+                    None => (false, item),
+                };
+                let (first, last) = match squares.split_once('-') {
+                    Some((first, last)) => (square(first)?, square(last)?),
+                    None => (square(squares)?, square(squares)?),
+                };
+                if first > last { return Err(FenError::Square(item.to_string())); }
+                for number in first..=last {
+                    let pack = environment.square_to_pack(number)
+                        .ok_or(FenError::OutOfBoard(number, environment.squares()))?;
+                    if pos.cells[pack].is_some() { return Err(FenError::Occupied(number)); }
+                    pos.insert_piece(Piece::new(pack, list_color, is_king));
+                }
+            }
+        }
+        if lists == 0 { return Err(FenError::Format(fen.to_string())); }
+        Ok(pos)
+    }
+
+    /// FEN string of the position, pieces of each side by ascending square number.
+    pub fn to_fen(&self) -> String {
+        let side = |color: Color| {
+            let mut pieces: Vec<_> = self.cells.iter().flatten()
+                .filter(|piece| piece.color == color)
+                .map(|piece| (self.environment.pack_to_square(piece.pos), piece.is_king))
+                .collect();
+            pieces.sort();
+            pieces.iter()
+                .map(|(square, is_king)| format!("{}{}", if *is_king { "K" } else { "" }, square))
+                .collect::<Vec<_>>().join(",")
+        };
+        let next_move = self.next_move.unwrap_or(self.environment.rules.first_move());
+        format!("{}:W{}:B{}",
+                if next_move == Color::White { "W" } else { "B" }, side(Color::White), side(Color::Black))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::color::Color;
+    use crate::fen::FenError;
+    use crate::game::Game;
+    use crate::position::Position;
+    use crate::position_environment::PositionEnvironment;
+    use crate::rules::Variant;
+
+    #[test]
+    fn square_numbers() {
+        let environment = PositionEnvironment::new(8);
+        // 1 is b8, 4 is h8, 29 is a1, 32 is g1
+        let board = |square| environment.pack_to_board[environment.square_to_pack(square).unwrap()];
+        assert_eq!((board(1), board(4), board(29), board(32)), (57, 63, 0, 6));
+        let environment = PositionEnvironment::for_variant(Variant::International);
+        let board = |square| environment.pack_to_board[environment.square_to_pack(square).unwrap()];
+        assert_eq!((board(1), board(5), board(46), board(50)), (91, 99, 0, 8));
+        assert_eq!(environment.square_to_pack(51), None);
+        for pos in 0..environment.squares() {
+            assert_eq!(environment.square_to_pack(environment.pack_to_square(pos)), Some(pos));
+        }
+    }
+
+    #[test]
+    fn standard_fen() {
+        let mut game = Game::new_standard(Variant::Russian);
+        assert_eq!(game.current_position.to_fen(),
+                   "W:W21,22,23,24,25,26,27,28,29,30,31,32:B1,2,3,4,5,6,7,8,9,10,11,12");
+        let environment = Arc::new(PositionEnvironment::for_variant(Variant::International));
+        let pos = Position::from_fen(environment, "W:W31-50:B1-20.").unwrap();
+        game.set_variant(Variant::International);
+        game.current_position = Position::initial(game.current_position.environment.clone());
+        assert!(pos == game.current_position);
+    }
+
+    #[test]
+    fn round_trip() {
+        let fen = "B:WK1,18,22:B5,K20,30";
+        let pos = Position::from_fen(Arc::new(PositionEnvironment::new(8)), fen).unwrap();
+        assert_eq!(pos.next_move, Some(Color::Black));
+        assert_eq!((pos.state.white.king, pos.state.white.simple), (1, 2));
+        assert_eq!((pos.state.black.king, pos.state.black.simple), (1, 2));
+        assert_eq!(pos.to_fen(), fen);
+        for variant in [Variant::Russian, Variant::International, Variant::Turkish, Variant::Canadian] {
+            let game = Game::new_standard(variant);
+            let fen = game.current_position.to_fen();
+            let mut other = Game::new_variant(variant);
+            other.set_fen(&fen).unwrap();
+            assert!(other.current_position == game.current_position, "{:?}", variant);
+            assert_eq!(other.fen(), fen);
+        }
+    }
+
+    #[test]
+    fn fen_errors() {
+        let environment = Arc::new(PositionEnvironment::new(8));
+        let error = |fen: &str| Position::from_fen(environment.clone(), fen).err().unwrap();
+        assert_eq!(error("W"), FenError::Format("W".to_string()));
+        assert_eq!(error("X:W1:B2"), FenError::Color("X".to_string()));
+        assert_eq!(error("W:Q1:B2"), FenError::PieceList("Q1".to_string()));
+        assert_eq!(error("W:W1,a:B2"), FenError::Square("a".to_string()));
+        assert_eq!(error("W:W1,33:B2"), FenError::OutOfBoard(33, 32));
+        assert_eq!(error("W:W1-3:B3"), FenError::Occupied(3));
+        assert_eq!(error("W:W0:B2").to_string(), "square 0 is out of the board, squares are 1-32");
+    }
+}
@@ -1,5 +1,5 @@
-use std::cell::{RefCell};
-use std::rc::Rc;
+use crate::shared::Shared;
+use std::cmp::Reverse;
 use std::sync::Arc;
 use wasm_bindgen::prelude::*;
 use crate::color::Color;
@@ -17,6 +17,12 @@ use crate::game::Method::{Deep, MCTS};
 use crate::PositionHistory::FinishType::{BlackWin, Draw1, Draw2, Draw3, Draw4, Draw5, WhiteWin};
 use crate::mcts::{McTree, Node};
 use crate::PositionHistory::{FinishType, PositionAndMove, PositionHistory};
+use crate::rules::Variant;
+use crate::fen::FenError;
+use crate::notation::{Notation, NotationError};
+use crate::pdn::{PdnError, PdnGame};
+use crate::search::SearchState;
+use crate::transposition::{Bound, DEFAULT_TT_SIZE, TranspositionTable};
 
 #[wasm_bindgen]
 pub struct MCTSRes {
@@ -25,7 +31,7 @@ pub struct MCTSRes {
     #[wasm_bindgen(skip)]
     pub finish: Option<FinishType>,
     #[wasm_bindgen(skip)]
-    pub pos_move: Option<Rc<RefCell<PositionAndMove>>>,
+    pub pos_move: Option<Shared<PositionAndMove>>,
 }
 
 #[wasm_bindgen]
@@ -33,9 +39,11 @@ pub struct MCTSRes {
 #[derive(TS)]
 #[ts(export)]
 pub struct BestPos {
-    pos: Option<Rc<RefCell<PositionAndMove>>>,
-    pos_list: Vec<Rc<RefCell<PositionAndMove>>>,
-    deep_eval: i32,
+    pos: Option<Shared<PositionAndMove>>,
+    pos_list: Vec<Shared<PositionAndMove>>,
+    pub(crate) deep_eval: i32,
+    /// moves the search expects from here, the first is the best move
+    pub(crate) pv: Vec<MoveItem>,
 }
 
 impl BestPos {
@@ -44,6 +52,12 @@ impl BestPos {
     }
 }
 
+// the move followed by the line found below it
+fn pv_line(pos_it: &Shared<PositionAndMove>, mut below: Vec<MoveItem>) -> Vec<MoveItem> {
+    below.insert(0, pos_it.borrow().mov.clone().unwrap());
+    below
+}
+
 #[wasm_bindgen]
 pub enum Method {
     Deep,
@@ -53,21 +67,45 @@ pub enum Method {
 
 
 pub struct Game {
-    pub position_history: Rc<RefCell<PositionHistory>>,
+    pub position_history: Shared<PositionHistory>,
     position_environment: Arc<PositionEnvironment>,
     pub current_position: Position,
-    max_depth: i16,
+    pub(crate) max_depth: i16,
     mcts_lim: i32,
     method: Method,
     pub tree: Option<McTree>,
+    // moves taken back by undo, the next one to redo last
+    redo_list: Vec<Shared<PositionAndMove>>,
+    // position before the first move of the history
+    pub(crate) start_position: Option<Position>,
+    // MCTS tree of the position undo left
+    undone_tree: Option<McTree>,
+    // limits and counters of `search`, unlimited outside of it
+    pub(crate) search: SearchState,
+    // positions searched by `best_move`, kept from one move to the next
+    pub(crate) tt: TranspositionTable,
 }
 
 
 impl Game {
     pub fn new(size: i8) -> Self {
-        let environment = Arc::new(PositionEnvironment::new(size));
+        Game::with_environment(Arc::new(PositionEnvironment::new(size)))
+    }
+
+    pub fn new_variant(variant: Variant) -> Self {
+        Game::with_environment(Arc::new(PositionEnvironment::for_variant(variant)))
+    }
+
+    /// The variant's starting position, ready for the first move.
+    pub fn new_standard(variant: Variant) -> Self {
+        let mut game = Game::new_variant(variant);
+        game.current_position = Position::initial(game.position_environment.clone());
+        game
+    }
+
+    fn with_environment(environment: Arc<PositionEnvironment>) -> Self {
         let position = Position::new(environment.clone());
-        let position_history = Rc::new(RefCell::new(PositionHistory::new()));
+        let position_history = Shared::new(PositionHistory::new());
         Game {
             position_environment: environment.clone(),
             current_position: position.clone(),
@@ -75,7 +113,12 @@ impl Game {
             max_depth: 3,
             method: Deep,
             mcts_lim: 10000,
-            tree: None
+            tree: None,
+            redo_list: vec![],
+            start_position: None,
+            undone_tree: None,
+            search: SearchState::default(),
+            tt: TranspositionTable::new(DEFAULT_TT_SIZE),
         }
     }
 
@@ -84,6 +127,11 @@ impl Game {
         self.max_depth = depth;
     }
 
+    /// Entries of the transposition table, rounded down to a power of two; 0 turns it off.
+    pub fn set_tt_size(&mut self, size: usize) {
+        self.tt = TranspositionTable::new(size);
+    }
+
     pub fn set_mcts_lim(&mut self, mcts_lim: i32) {
         self.mcts_lim = mcts_lim;
     }
@@ -93,6 +141,64 @@ impl Game {
         self.method = method;
     }
 
+    /// Switches the rules, starting over on an empty board of the variant's size.
+    pub fn set_variant(&mut self, variant: Variant) {
+        let mut game = Game::new_variant(variant);
+        game.max_depth = self.max_depth;
+        game.mcts_lim = self.mcts_lim;
+        game.tt = TranspositionTable::new(self.tt.size());
+        game.method = std::mem::replace(&mut self.method, Deep);
+        *self = game;
+    }
+
+    /// Starts over from the FEN position, keeping the rules.
+    pub fn set_fen(&mut self, fen: &str) -> Result<(), FenError> {
+        self.current_position = Position::from_fen(self.position_environment.clone(), fen)?;
+        self.position_history = Shared::new(PositionHistory::new());
+        self.tree = None;
+        self.forget_redo();
+        self.start_position = None;
+        Ok(())
+    }
+
+    pub fn set_fen_ts_n(&mut self, fen: &str) -> Result<JsValue, JsValue> {
+        match self.set_fen(fen) {
+            Ok(()) => Ok(JsValue::TRUE),
+            Err(err) => Err(JsValue::from_str(&err.to_string())),
+        }
+    }
+
+    pub fn fen(&self) -> String {
+        self.current_position.to_fen()
+    }
+
+    /// Game replayed from the main line of the first PDN game in the text.
+    pub fn from_pdn(text: &str) -> Result<Game, PdnError> {
+        PdnGame::parse(text)?.to_game()
+    }
+
+    /// Replaces the rules, the position and the history with the PDN game, keeping the search settings.
+    pub fn load_pdn(&mut self, text: &str) -> Result<(), PdnError> {
+        let mut game = Game::from_pdn(text)?;
+        game.max_depth = self.max_depth;
+        game.mcts_lim = self.mcts_lim;
+        game.tt = TranspositionTable::new(self.tt.size());
+        game.method = std::mem::replace(&mut self.method, Deep);
+        *self = game;
+        Ok(())
+    }
+
+    pub fn load_pdn_ts_n(&mut self, text: &str) -> Result<JsValue, JsValue> {
+        match self.load_pdn(text) {
+            Ok(()) => Ok(JsValue::TRUE),
+            Err(err) => Err(JsValue::from_str(&err.to_string())),
+        }
+    }
+
+    pub fn to_pdn(&self) -> String {
+        PdnGame::from_game(self).to_string()
+    }
+
     pub fn insert_piece(&mut self, piece: Piece) {
         self.current_position.insert_piece(piece);
     }
@@ -110,20 +216,56 @@ impl Game {
     }
 
     pub fn make_move_by_pos_item(&mut self, pos: &BestPos) {
-        self.current_position.make_move(&mut pos.get_move_item());
-        self.position_history.borrow_mut().push(PositionAndMove::from(self.current_position.clone(), pos.get_move_item()));
+        self.make_move_by_move_item(&pos.get_move_item());
     }
 
+    /// Plays the move; the moves taken back by `undo` are kept if it is the next of them.
     pub fn make_move_by_move_item(&mut self, move_item: &MoveItem) -> Option<FinishType> {
+        if self.redo_list.last().is_some_and(|x| x.borrow().mov.as_ref() == Some(move_item)) {
+            self.redo(1);
+            return self.position_history.borrow_mut().finish_check();
+        }
+        self.forget_redo();
+        if self.position_history.borrow().list.is_empty() {
+            self.start_position = Some(self.current_position.clone());
+        }
         self.current_position.make_move(move_item);
         self.position_history.borrow_mut()
             .push(PositionAndMove::from(self.current_position.clone(), move_item.clone()))
     }
 
+    /// Plays the move written as `c3-d4` or `22x13x6`.
+    pub fn make_move_by_notation(&mut self, text: &str) -> Result<Option<FinishType>, NotationError> {
+        let move_item = self.current_position.parse_move(text)?;
+        Ok(self.make_move_by_move_item(&move_item))
+    }
+
+    pub fn make_move_by_notation_ts_n(&mut self, text: &str) -> Result<JsValue, JsValue> {
+        match self.make_move_by_notation(text) {
+            Ok(None) => Ok(JsValue::TRUE),
+            Ok(Some(finish)) => Ok(serde_wasm_bindgen::to_value(&finish)?),
+            Err(err) => Err(JsValue::from_str(&err.to_string())),
+        }
+    }
+
+    /// Legal moves as text, in the order of the move list.
+    pub fn get_move_list_notation_ts_n(&mut self, notation: Notation) -> JsValue {
+        let list = self.get_move_list(false);
+        let text: Vec<_> = list.list.iter().map(|x| x.notation(&self.position_environment, notation)).collect();
+        match serde_wasm_bindgen::to_value(&text) {
+            Ok(js) => js,
+            Err(_err) => JsValue::UNDEFINED,
+        }
+    }
+
 
     pub fn best_move(&mut self, mut max_depth: i16, mut best_white: i32,
                      mut best_black: i32, depth: i16, state_only: bool) -> BestPos {
         // log(&format!("{:?}", self.current_position));
+        if !self.search.visit() {
+            // out of budget, the search throws this iteration away
+            return BestPos { pos: None, pos_list: vec![], deep_eval: 0, pv: vec![] };
+        }
         let finish = self.position_history.borrow_mut().finish_check();
         if finish.is_some() {
             // print!("{:?} {}\n", finish, depth);
@@ -131,71 +273,101 @@ impl Game {
             let pos_it = self.position_history.borrow_mut().last();
             let eval = pos_it.borrow_mut().pos.evaluate(state_only);
             pos_it.borrow_mut().deep_eval = Option::from(eval);
-            return BestPos { deep_eval: eval, pos_list: vec![pos_it.clone()], pos: Option::from(pos_it) };
+            return BestPos { deep_eval: eval, pos_list: vec![pos_it.clone()], pos: Option::from(pos_it), pv: vec![] };
+        }
+        let key = TranspositionTable::key(self.current_position.hash(), state_only);
+        let (remaining, alpha, beta) = (max_depth - depth, best_white, best_black);
+        // a score found on another path may miss a draw this one leads to
+        let tt_usable = !self.position_history.borrow().draw_counts_history();
+        let mut tt_move = None;
+        if let Some(entry) = self.tt.probe(key).filter(|_| tt_usable) {
+            if depth > 0 && entry.cuts(remaining, alpha, beta) {
+                let (deep_eval, exact) = (entry.score, entry.bound == Bound::Exact);
+                self.search.tt_cutoffs += 1;
+                let pv = if exact { self.tt_line(remaining, state_only) } else { vec![] };
+                return BestPos { pos: None, pos_list: vec![], deep_eval, pv };
+            }
+            tt_move = entry.mov.clone();
         }
         let ref move_list = self.current_position.get_move_list_cached();
-        let mut pos_list: Vec<_> = {
-            move_list.as_ref().as_ref().unwrap().list.iter().map(|x| {
-                let mut pos = self.current_position.make_move_and_get_position(x);
-                pos.pos.evaluate(state_only);
-                self.current_position.unmake_move(x);
-                Rc::new(RefCell::new(pos))
-            }).collect()
-        };
-        if pos_list.len() == 0 { panic!("Best move: it`s standoff position") }
+        let list = &move_list.as_ref().as_ref().unwrap().list;
+        if list.is_empty() { panic!("Best move: it`s standoff position") }
         let move_color = self.current_position.next_move.unwrap();
-        if pos_list.len() < 3 { max_depth += 1; }
-        pos_list.sort_by_key(|x|
-            x.borrow().pos.eval.unwrap() * if move_color == White { -1 } else { 1 });
-        let res_pos_list = pos_list.clone();
+        if list.len() < 3 { max_depth += 1; }
+        // the best move of the previous iteration goes first, then the one stored in the table
+        let first_move = if depth == 0 && self.search.best_move.is_some() { self.search.best_move.clone() } else { tt_move };
+        let mut moves: Vec<_> = list.iter()
+            // the lines already found by multi-PV
+            .filter(|x| depth > 0 || !self.search.excluded.contains(x))
+            .collect();
+        moves.sort_by_cached_key(|x| Reverse(self.search.order_key(x, depth, first_move.as_ref())));
+        let mut pos_list = vec![];
         let mut best_pos = BestPos {
             pos: None,
-            pos_list: res_pos_list.clone(),
+            pos_list: vec![],
             deep_eval: if move_color == White { i32::MIN / 2 } else { i32::MAX / 2 },
+            pv: vec![],
         };
         if depth < max_depth {
-            for pos_it in &pos_list {
-                self.current_position.make_move(pos_it.borrow().mov.as_ref().unwrap());
-                self.position_history.borrow_mut().push_rc(pos_it.clone());
-                let deep_eval =
-                    self.best_move(max_depth, best_white, best_black, depth + 1, state_only).deep_eval;
+            for (i, &mov) in moves.iter().enumerate() {
+                let pos_it = Shared::new(self.current_position.make_move_and_get_position(mov));
+                pos_list.push(pos_it.clone());
+                self.position_history.borrow_mut().push_rc(pos_it);
+                let below = self.best_move(max_depth, best_white, best_black, depth + 1, state_only);
+                let deep_eval = below.deep_eval;
                 let pos_it = self.position_history.borrow_mut().pop().unwrap();
                 pos_it.borrow_mut().deep_eval = Option::from(deep_eval);
                 self.current_position.took_pieces = pos_it.borrow().pos.took_pieces.clone();
                 self.current_position.unmake_move(&pos_it.borrow().mov.as_ref().unwrap());
                 let white = self.current_position.state.white.clone();
                 let black = self.current_position.state.black.clone();
+                let king_moves = (self.current_position.state.white_king_moves, self.current_position.state.black_king_moves);
                 self.current_position.state = pos_it.borrow().pos.state.clone();
                 self.current_position.state.white = white;
                 self.current_position.state.black = black;
+                (self.current_position.state.white_king_moves, self.current_position.state.black_king_moves) = king_moves;
+                // the budget ran out below, the score of the move is not real
+                if self.search.stopped { break; }
                 if move_color == White {
                     if best_black < deep_eval {
                         // print!("cut at white move depth: {} {} {} {}\n", depth, best_black, best_white, deep_eval);
-                        return BestPos { pos: Some(pos_it), pos_list: res_pos_list.clone(), deep_eval };
+                        self.search.cutoff(mov, depth, max_depth - depth, i == 0);
+                        best_pos = BestPos { pv: pv_line(&pos_it, below.pv), pos: Some(pos_it), pos_list: vec![], deep_eval };
+                        break;
                     }
                     if best_white < deep_eval { best_white = deep_eval }
                     if best_pos.deep_eval < deep_eval {
-                        best_pos = BestPos { pos: Option::from(pos_it), pos_list: res_pos_list.clone(), deep_eval };
+                        best_pos = BestPos { pv: pv_line(&pos_it, below.pv), pos: Option::from(pos_it), pos_list: vec![], deep_eval };
                     }
                 } else {
                     if best_white > deep_eval {
                         // print!("cut at black move depth: {} {} {} {}\n", depth, best_black, best_white, deep_eval);
-                        return BestPos { pos: Option::from(pos_it), pos_list: res_pos_list.clone(), deep_eval };
+                        self.search.cutoff(mov, depth, max_depth - depth, i == 0);
+                        best_pos = BestPos { pv: pv_line(&pos_it, below.pv), pos: Option::from(pos_it), pos_list: vec![], deep_eval };
+                        break;
                     }
                     if best_black > deep_eval { best_black = deep_eval }
                     if best_pos.deep_eval > deep_eval {
-                        best_pos = BestPos { pos: Option::from(pos_it), pos_list: res_pos_list.clone(), deep_eval };
+                        best_pos = BestPos { pv: pv_line(&pos_it, below.pv), pos: Option::from(pos_it), pos_list: vec![], deep_eval };
                     }
                 }
             }
         } else {
-            let pos = pos_list.pop().unwrap();
-            let eval = pos.borrow_mut().pos.evaluate(state_only);
-            best_pos = BestPos {
-                deep_eval: eval,
-                pos_list: res_pos_list.clone(),
-                pos: Some(pos),
+            // only the root needs a move to return
+            if depth == 0 {
+                let pos = Shared::new(self.current_position.make_move_and_get_position(moves[0]));
+                self.current_position.unmake_move(moves[0]);
+                pos_list.push(pos.clone());
+                best_pos.pos = Some(pos);
             }
+            // the captures left are played out, a position in the middle of an exchange is not scored
+            best_pos.deep_eval = self.current_position.quiescence(best_white, best_black, state_only);
+        }
+        best_pos.pos_list = pos_list;
+        // an iteration stopped by the search limits leaves wrong scores
+        if tt_usable && !self.search.stopped && (depth > 0 || self.search.excluded.is_empty()) {
+            let mov = if depth < max_depth { best_pos.pos.as_ref().and_then(|x| x.borrow().mov.clone()) } else { None };
+            self.tt.store(key, remaining, TranspositionTable::bound(best_pos.deep_eval, alpha, beta), best_pos.deep_eval, mov);
         }
         best_pos
     }
@@ -212,6 +384,7 @@ impl Game {
         }
         let best_move = match self.method {
             Deep => {
+                self.tt.new_search();
                 let best_move = self.best_move(self.max_depth, i32::MIN / 2, i32::MAX / 2, 0, false);
                 if apply {
                     self.make_best_move(&best_move);
@@ -220,11 +393,11 @@ impl Game {
             }
             MCTS => {
                 let best = self.find_mcts_and_make_best_move(apply).await;
-                BestPos { pos: best.pos_move, pos_list: vec![], deep_eval: 0 }
+                BestPos { pos: best.pos_move, pos_list: vec![], deep_eval: 0, pv: vec![] }
             }
             Method::Mix => {
                 let best = self.find_mcts_and_make_best_move(apply).await;
-                BestPos { pos: best.pos_move, pos_list: vec![], deep_eval: 0 }
+                BestPos { pos: best.pos_move, pos_list: vec![], deep_eval: 0, pv: vec![] }
             }
         };
         match serde_wasm_bindgen::to_value(
@@ -250,7 +423,7 @@ impl Game {
         }
         let mut board_list: Vec<Vec<i32>> = vec![];
         for pos in pos_list {
-            let mut board = vec![0; (self.position_environment.size * self.position_environment.size / 2) as usize];
+            let mut board = vec![0; self.position_environment.pack_to_board.len()];
             for cell in &pos.pos.cells {
                 if let Some(piece) = cell {
                     board[piece.pos] =
@@ -271,6 +444,7 @@ impl Game {
                 Err(_err) => JsValue::UNDEFINED
             };
         }
+        self.tt.new_search();
         let best_pos = self.best_move(self.max_depth, i32::MIN / 2, i32::MAX / 2, 0, false);
         self.make_move_by_pos_item(&best_pos);
         let finish = self.position_history.borrow_mut().finish_check();
@@ -289,7 +463,7 @@ impl Game {
 
 
 
-    fn apply_node_move(&mut self, node: Rc<RefCell<Node>>) {
+    fn apply_node_move(&mut self, node: Shared<Node>) {
         self.current_position = node.clone().borrow().pos_mov.borrow().pos.clone();
         self.position_history.borrow_mut().push_rc(
             node.clone().borrow().pos_mov.clone());
@@ -321,9 +495,7 @@ impl Game {
         if self.tree.as_ref().unwrap().root.borrow().pos_mov.borrow().pos != self.current_position {
             if let Some(tree) = &self.tree {
                 tree.root.borrow_mut().expand();
-                let chs = tree.tree_childs();
-                let node =
-                    chs.iter().find(|x| x.borrow().pos_mov.borrow().pos == self.current_position);
+                let node = tree.root.borrow().childs.get(&self.current_position.hash()).cloned();
                 if node.is_some() {
                     self.tree.as_mut().unwrap().root = node.unwrap().clone();
                 } else {
@@ -404,7 +576,7 @@ impl Game {
                 if childs.len() == 1 { n_max *= 2; }
                 let mut board_list: Vec<Vec<i32>> = vec![];
                 for child in childs {
-                    let mut board = vec![0 as i32; (self.position_environment.size * self.position_environment.size / 2) as usize];
+                    let mut board = vec![0_i32; self.position_environment.pack_to_board.len()];
                     for cell in &child.borrow().get_pos_mov().borrow().pos.cells {
                         if let Some(piece) = cell {
                             board[piece.pos] =
@@ -433,14 +605,78 @@ impl Game {
 
     pub fn mov_back(&mut self) {
         if self.position_history.borrow().list.len() > 1 {
-            let mut pop = self.position_history.borrow_mut().pop();
-            pop = self.position_history.borrow_mut().pop();
-            self.current_position = pop.clone().unwrap().borrow().pos.clone();
-            self.current_position.unmake_move(&pop.unwrap().borrow().mov.clone().unwrap());
-            self.tree = None;
+            self.undo(2);
+        }
+    }
+
+    /// Number of moves made.
+    pub fn ply(&self) -> usize {
+        self.position_history.borrow().list.len()
+    }
+
+    /// Number of moves `redo` can make again.
+    pub fn redo_len(&self) -> usize {
+        self.redo_list.len()
+    }
+
+    /// Takes back up to `n` moves, returns how many were taken back.
+    pub fn undo(&mut self, n: usize) -> usize {
+        let n = n.min(self.ply());
+        if n == 0 { return 0; }
+        if self.tree.is_some() { self.undone_tree = self.tree.take(); }
+        for _ in 0..n {
+            let item = self.position_history.borrow_mut().pop().unwrap();
+            self.redo_list.push(item);
+        }
+        self.current_position = match self.position_history.borrow().list.last() {
+            Some(item) => item.borrow().pos.clone(),
+            None => self.start_position.clone().unwrap_or_else(|| {
+                let first = self.redo_list.last().unwrap().borrow();
+                let mut pos = first.pos.clone();
+                pos.unmake_move(first.mov.as_ref().unwrap());
+                pos
+            }),
+        };
+        n
+    }
+
+    /// Makes again up to `n` moves taken back by `undo`, returns how many were made.
+    /// The MCTS tree follows if it has explored them.
+    pub fn redo(&mut self, n: usize) -> usize {
+        let n = n.min(self.redo_list.len());
+        for _ in 0..n {
+            let item = self.redo_list.pop().unwrap();
+            self.current_position = item.borrow().pos.clone();
+            self.position_history.borrow_mut().push_rc(item);
+            let child = self.tree.as_ref()
+                .and_then(|tree| tree.root.borrow().childs.get(&self.current_position.hash()).cloned());
+            match child {
+                Some(child) => self.tree.as_mut().unwrap().root = child,
+                None => self.tree = None,
+            }
+            if self.tree.is_none() && self.undone_tree.as_ref()
+                .is_some_and(|tree| tree.root.borrow().pos_mov.borrow().pos == self.current_position) {
+                self.tree = self.undone_tree.take();
+            }
+        }
+        n
+    }
+
+    /// Goes to the position after `ply` moves, back or along the moves taken back.
+    pub fn goto_ply(&mut self, ply: usize) -> bool {
+        let current = self.ply();
+        if ply <= current {
+            self.undo(current - ply);
+        } else {
+            self.redo(ply - current);
         }
+        self.ply() == ply
     }
 
+    pub(crate) fn forget_redo(&mut self) {
+        self.redo_list.clear();
+        self.undone_tree = None;
+    }
 
     pub fn move_by_tree_index_ts_n(&mut self, i: usize) -> JsValue {
         return match serde_wasm_bindgen::to_value(&self.move_by_tree_index(i)) {
@@ -481,6 +717,7 @@ impl Game {
 
 
     pub fn get_best_move_rust(&mut self) -> BestPos {
+        self.tt.new_search();
         self.best_move(self.max_depth, i32::MIN / 2, i32::MAX / 2, 0, false)
     }
 
@@ -545,7 +782,7 @@ impl Game {
         if !pos_list.is_empty() {
             if self.current_position.cells[pos_list[0] as usize].is_some() {
                 let move_list = self.get_move_list(true);
-                for mut move_item in move_list.list {
+                for move_item in move_list.list {
                     let mut i = 1;
                     let mut ok = true;
                     for mov in &move_item {
@@ -560,10 +797,8 @@ impl Game {
                         i += 1;
                     }
                     if ok && pos_list.len() == i {
-                        self.current_position.make_move(&mut move_item);
                         let draw = self.position_history.borrow_mut().finish_check();
-                        self.position_history.borrow_mut().push(
-                            PositionAndMove::from(self.current_position.clone(), move_item));
+                        self.make_move_by_move_item(&move_item);
                         return if draw.is_none() { Ok(JsValue::TRUE) } else {
                             Ok(serde_wasm_bindgen::to_value(&draw.unwrap()).unwrap())
                         };
@@ -582,9 +817,13 @@ mod tests {
     use js_sys::Math::min;
     use crate::color::Color;
     use crate::game::Game;
+    use crate::mcts::McTree;
     use crate::PositionHistory::FinishType::{BlackWin, Draw1, Draw2, Draw3, WhiteWin};
+    use crate::PositionHistory::PositionHistory;
     use crate::piece::Piece;
     use crate::position_environment::PositionEnvironment;
+    use crate::rules::Variant;
+    use crate::shared::Shared;
 
     #[test]
     fn game_test() {
@@ -645,6 +884,85 @@ mod tests {
     }
 
 
+    fn played(moves: &[&str]) -> Game {
+        let mut game = Game::new_standard(Variant::Russian);
+        moves.iter().for_each(|text| { game.make_move_by_notation(text).unwrap(); });
+        game
+    }
+
+    #[test]
+    fn undo_redo() {
+        let moves = ["c3-d4", "f6-g5", "g3-f4", "g7-f6", "d4-c5", "b6xd4", "e3xc5"];
+        let mut game = played(&[]);
+        let mut states = vec![(game.fen(), format!("{:?}", game.current_position.state))];
+        for text in moves {
+            game.make_move_by_notation(text).unwrap();
+            states.push((game.fen(), format!("{:?}", game.current_position.state)));
+        }
+        let state = |game: &Game| (game.fen(), format!("{:?}", game.current_position.state));
+        assert_eq!(game.undo(3), 3);
+        assert_eq!((game.ply(), game.redo_len()), (4, 3));
+        assert_eq!(state(&game), states[4]);
+        assert_eq!(game.redo(10), 3);
+        assert_eq!(state(&game), states[7]);
+        for ply in [0, 5, 2, 7, 1] {
+            assert!(game.goto_ply(ply));
+            assert_eq!(state(&game), states[ply]);
+            assert_eq!(game.position_history.borrow().len(), ply);
+        }
+        assert!(!game.goto_ply(8));
+        assert_eq!(game.ply(), 7);
+    }
+
+    #[test]
+    fn redo_until_other_move() {
+        let mut game = played(&["c3-d4", "f6-g5", "g3-f4"]);
+        game.undo(3);
+        // the same move keeps the rest of the moves to redo
+        game.make_move_by_notation("c3-d4").unwrap();
+        assert_eq!(game.redo_len(), 2);
+        game.make_move_by_notation("f6-e5").unwrap();
+        assert_eq!(game.redo_len(), 0);
+        assert_eq!(game.redo(1), 0);
+        game.mov_back();
+        assert_eq!((game.ply(), game.redo_len()), (0, 2));
+        assert!(game.current_position == Game::new_standard(Variant::Russian).current_position);
+    }
+
+    #[test]
+    fn redo_keeps_tree() {
+        let mut game = played(&["c3-d4", "f6-g5"]);
+        game.undo(1);
+        game.init_tree();
+        game.tree.as_ref().unwrap().root.borrow_mut().expand();
+        let root = game.tree.as_ref().unwrap().root.clone();
+        game.redo(1);
+        let child = game.tree.as_ref().unwrap().root.clone();
+        assert!(root.borrow().childs.values().any(|x| Shared::ptr_eq(x, &child)));
+        game.undo(2);
+        assert!(game.tree.is_none());
+        game.redo(2);
+        assert!(Shared::ptr_eq(&game.tree.as_ref().unwrap().root, &child));
+    }
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn send_to_thread() {
+        assert_send::<Game>();
+        assert_send::<McTree>();
+        assert_send::<PositionHistory>();
+        let mut game = played(&["c3-d4"]);
+        game.init_tree();
+        game.tree.as_ref().unwrap().root.borrow_mut().expand();
+        let game = std::thread::spawn(move || {
+            let best = game.get_best_move_rust();
+            game.make_best_move(&best);
+            game
+        }).join().unwrap();
+        assert_eq!(game.position_history.borrow().len(), 2);
+    }
+
     #[test]
     fn finish_cmp() {
         assert_eq!(Draw2, Draw1);
@@ -0,0 +1,284 @@
+use crate::game::Game;
+use crate::moves_list::MoveItem;
+use crate::position::Position;
+use crate::PositionHistory::{PositionAndMove, PositionHistory};
+use crate::shared::Shared;
+
+pub type NodeId = usize;
+
+/// A position of the record with the move that led to it.
+#[derive(Debug, Clone)]
+pub struct GameNode {
+    pub parent: Option<NodeId>,
+    /// continuations, the first one is the main line
+    pub children: Vec<NodeId>,
+    pub mov: Option<MoveItem>,
+    pub pos: Position,
+    pub comment: Option<String>,
+    /// PDN annotation glyphs: 1 `!`, 2 `?`, 3 `!!`, 4 `??`, 5 `!?`, 6 `?!`
+    pub nags: Vec<u32>,
+    pub eval: Option<i32>,
+}
+
+/// Game record with variations. Node ids stay valid until their node is deleted.
+#[derive(Debug, Clone)]
+pub struct GameTree {
+    nodes: Vec<Option<GameNode>>,
+    pub current: NodeId,
+}
+
+impl GameTree {
+    pub fn new(start: Position) -> GameTree {
+        GameTree {
+            nodes: vec![Some(GameNode {
+                parent: None,
+                children: vec![],
+                mov: None,
+                pos: start,
+                comment: None,
+                nags: vec![],
+                eval: None,
+            })],
+            current: 0,
+        }
+    }
+
+    /// The game's moves as the main line from the position it started at, `current` at its end.
+    pub fn from_game(game: &Game) -> GameTree {
+        let history = game.position_history.borrow();
+        let start = match history.list.first() {
+            Some(first) => game.start_position.clone().unwrap_or_else(|| {
+                let first = first.borrow();
+                let mut pos = first.pos.clone();
+                pos.unmake_move(first.mov.as_ref().unwrap());
+                pos
+            }),
+            None => game.current_position.clone(),
+        };
+        let mut tree = GameTree::new(start);
+        for item in &history.list {
+            tree.add_move(item.borrow().mov.as_ref().unwrap());
+        }
+        tree
+    }
+
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&GameNode> {
+        self.nodes.get(id).and_then(Option::as_ref)
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut GameNode> {
+        self.nodes.get_mut(id).and_then(Option::as_mut)
+    }
+
+    fn get(&self, id: NodeId) -> &GameNode {
+        self.node(id).unwrap_or_else(|| panic!("no node {}", id))
+    }
+
+    /// Plays the move from the current node. A move already in the tree is reused,
+    /// a new one becomes the main line if the node had no continuation, a variation otherwise.
+    pub fn add_move(&mut self, mov: &MoveItem) -> NodeId {
+        let current = self.get(self.current);
+        let same = current.children.iter().find(|x| self.get(**x).mov.as_ref() == Some(mov)).copied();
+        if let Some(child) = same {
+            self.current = child;
+            return child;
+        }
+        let mut pos = current.pos.clone();
+        pos.make_move(mov);
+        let id = self.nodes.len();
+        self.nodes.push(Some(GameNode {
+            parent: Some(self.current),
+            children: vec![],
+            mov: Some(mov.clone()),
+            pos,
+            comment: None,
+            nags: vec![],
+            eval: None,
+        }));
+        let parent = self.current;
+        self.node_mut(parent).unwrap().children.push(id);
+        self.current = id;
+        id
+    }
+
+    pub fn go_to(&mut self, id: NodeId) -> bool {
+        if self.node(id).is_none() { return false; }
+        self.current = id;
+        true
+    }
+
+    pub fn back(&mut self) -> bool {
+        match self.get(self.current).parent {
+            Some(parent) => self.go_to(parent),
+            None => false,
+        }
+    }
+
+    /// Steps along the main continuation.
+    pub fn forward(&mut self) -> bool {
+        match self.get(self.current).children.first() {
+            Some(child) => self.go_to(*child),
+            None => false,
+        }
+    }
+
+    /// Nodes from the root to `id`, both included.
+    pub fn path(&self, id: NodeId) -> Vec<NodeId> {
+        let mut path = vec![id];
+        while let Some(parent) = self.get(*path.last().unwrap()).parent {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+
+    /// `id` and the main continuation after it.
+    pub fn main_line(&self, id: NodeId) -> Vec<NodeId> {
+        let mut line = vec![id];
+        while let Some(child) = self.get(*line.last().unwrap()).children.first() {
+            line.push(*child);
+        }
+        line
+    }
+
+    /// Makes the line through `id` the main line.
+    pub fn promote(&mut self, id: NodeId) -> bool {
+        if self.node(id).is_none() { return false; }
+        let path = self.path(id);
+        for pair in path.windows(2) {
+            let children = &mut self.node_mut(pair[0]).unwrap().children;
+            let i = children.iter().position(|x| *x == pair[1]).unwrap();
+            let child = children.remove(i);
+            children.insert(0, child);
+        }
+        true
+    }
+
+    /// Removes the node and everything after it; the root can't be deleted.
+    pub fn delete(&mut self, id: NodeId) -> bool {
+        let parent = match self.node(id).and_then(|x| x.parent) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        if self.path(self.current).contains(&id) { self.current = parent; }
+        self.node_mut(parent).unwrap().children.retain(|x| *x != id);
+        let mut stack = vec![id];
+        while let Some(next) = stack.pop() {
+            stack.extend(self.nodes[next].take().unwrap().children);
+        }
+        true
+    }
+
+    /// The moves up to `id` the way `Game` keeps them.
+    pub fn history(&self, id: NodeId) -> PositionHistory {
+        let mut history = PositionHistory::new();
+        for node in self.path(id).iter().skip(1).map(|x| self.get(*x)) {
+            history.push(PositionAndMove::from(node.pos.clone(), node.mov.clone().unwrap()));
+        }
+        history
+    }
+}
+
+impl Game {
+    /// Continues from the tree node: its position with the moves leading to it as history.
+    pub fn follow(&mut self, tree: &GameTree, id: NodeId) {
+        self.current_position = tree.get(id).pos.clone();
+        self.position_history = Shared::new(tree.history(id));
+        self.tree = None;
+        self.forget_redo();
+        self.start_position = Some(tree.get(tree.root()).pos.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Game;
+    use crate::game_tree::GameTree;
+    use crate::rules::Variant;
+
+    fn play(tree: &mut GameTree, moves: &[&str]) -> Vec<usize> {
+        moves.iter().map(|text| {
+            let mov = tree.node(tree.current).unwrap().pos.clone().parse_move(text).unwrap();
+            tree.add_move(&mov)
+        }).collect()
+    }
+
+    #[test]
+    fn variations() {
+        let game = Game::new_standard(Variant::Russian);
+        let mut tree = GameTree::new(game.current_position.clone());
+        let main = play(&mut tree, &["c3-d4", "f6-g5", "g3-f4"]);
+        tree.go_to(main[0]);
+        let side = play(&mut tree, &["h6-g5", "a3-b4"]);
+        // the same move again goes into the existing node
+        tree.go_to(main[0]);
+        assert_eq!(play(&mut tree, &["f6-g5"]), vec![main[1]]);
+        assert_eq!(tree.node(main[0]).unwrap().children, vec![main[1], side[0]]);
+        assert_eq!(tree.main_line(tree.root())[1..], main[..]);
+        assert_eq!(tree.path(side[1]), vec![0, main[0], side[0], side[1]]);
+        tree.go_to(side[1]);
+        assert!(tree.back() && tree.back() && tree.forward());
+        assert_eq!(tree.current, main[1]);
+        tree.promote(side[1]);
+        assert_eq!(tree.main_line(tree.root())[1..], [main[0], side[0], side[1]]);
+    }
+
+    #[test]
+    fn delete_subtree() {
+        let mut tree = GameTree::new(Game::new_standard(Variant::Russian).current_position);
+        let main = play(&mut tree, &["c3-d4", "f6-g5", "g3-f4"]);
+        tree.go_to(main[0]);
+        let side = play(&mut tree, &["h6-g5", "a3-b4"]);
+        assert!(tree.delete(side[0]));
+        assert_eq!(tree.current, main[0]);
+        assert!(tree.node(side[1]).is_none());
+        assert!(!tree.go_to(side[1]));
+        assert!(!tree.delete(tree.root()));
+        assert!(tree.delete(main[1]));
+        assert!(tree.node(main[0]).unwrap().children.is_empty());
+    }
+
+    #[test]
+    fn follow_node() {
+        let mut game = Game::new_standard(Variant::Russian);
+        for text in ["c3-d4", "f6-g5", "g3-f4"] {
+            game.make_move_by_notation(text).unwrap();
+        }
+        let mut tree = GameTree::from_game(&game);
+        assert_eq!(tree.main_line(tree.root()).len(), 4);
+        tree.back();
+        let node = play(&mut tree, &["d4-c5"])[0];
+        tree.node_mut(node).unwrap().nags.push(2);
+        let mut other = Game::new_standard(Variant::Russian);
+        other.follow(&tree, node);
+        assert_eq!(other.position_history.borrow().len(), 3);
+        let mut game = Game::new_standard(Variant::Russian);
+        for text in ["c3-d4", "f6-g5", "d4-c5"] {
+            game.make_move_by_notation(text).unwrap();
+        }
+        assert!(other.current_position == game.current_position);
+    }
+
+    #[test]
+    fn from_fen_start() {
+        let mut game = Game::new_variant(Variant::Russian);
+        // the first move is a capture, the start can't be found by taking it back
+        game.set_fen("W:W22,29:B6,18").unwrap();
+        for text in ["c3xe5", "c7-d6", "e5xc7"] {
+            game.make_move_by_notation(text).unwrap();
+        }
+        let tree = GameTree::from_game(&game);
+        assert!(tree.node(tree.root()).unwrap().pos.to_fen() == "W:W22,29:B6,18");
+        let pdn = game.to_pdn();
+        assert!(pdn.contains("[FEN \"W:W22,29:B6,18\"]"), "{}", pdn);
+        let other = Game::from_pdn(&pdn).unwrap();
+        assert!(other.current_position == game.current_position, "{}", pdn);
+        let (a, b) = (game.position_history.borrow(), other.position_history.borrow());
+        assert_eq!(a.len(), b.len());
+        assert!(a.list.iter().zip(&b.list).all(|(x, y)| x.borrow().pos == y.borrow().pos));
+    }
+}
@@ -14,6 +14,18 @@ pub mod PositionHistory;
 pub mod cache_map;
 mod loop_array;
 pub mod cache_db;
+pub mod rules;
+pub mod fen;
+pub mod notation;
+pub mod pdn;
+pub mod game_tree;
+pub mod zobrist;
+pub mod bitboard;
+pub mod perft;
+pub mod shared;
+pub mod search;
+pub mod transposition;
+pub mod quiescence;
 
 
 #[wasm_bindgen]
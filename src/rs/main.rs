@@ -11,12 +11,14 @@ use crate::mcts::{Cache, NodeCacheItem, Node, OldCacheItem, PositionWN};
 use crate::piece::Piece;
 use rayon::prelude::*;
 use std::iter::Iterator;
+use std::time::Instant;
 use std::ops::{Deref, DerefMut};
 use schemars::_private::NoSerialize;
 use tokio::runtime::Runtime;
 use crate::cache_db::CacheDb;
 use crate::PositionHistory::FinishType;
 use crate::PositionHistory::FinishType::{BlackWin, WhiteWin};
+use crate::rules::Variant;
 include!("lib.rs");
 #[derive(Debug)]
 pub struct Score {
@@ -41,20 +43,13 @@ struct MoveAsQuite {
 }
 
 pub fn init(game: &mut Game) {
-    *game = Game::new(8);
-    vec![0, 2, 4, 6, 9, 11, 13, 15, 16, 18, 20, 22].iter()
-        .for_each(|pos|
-            game.insert_piece(Piece::new(game.to_pack(*pos), Color::White, false)));
-    vec![0, 2, 4, 6, 9, 11, 13, 15, 16, 18, 20, 22].iter().map(|x| 63 - x).collect::<Vec<_>>().iter()
-        .for_each(|pos|
-            game.insert_piece(Piece::new(game.to_pack(*pos), Color::Black, false)));
+    *game = Game::new_standard(Variant::Russian);
     // vec![0, 2, 4].iter()
     //     .for_each(|pos|
     //         game.insert_piece(Piece::new(game.to_pack(*pos), Color::White, true)));
     // vec![15].iter().map(|x| 63 - x).collect::<Vec<_>>().iter()
     //     .for_each(|pos|
     //         game.insert_piece(Piece::new(game.to_pack(*pos), Color::Black, true)));
-    game.current_position.next_move = Option::from(Color::White);
     // vec![4].iter()
     //     .for_each(|pos|
     //         game.insert_piece(Piece::new(game.to_pack(*pos), Color::Black, true)));
@@ -216,20 +211,9 @@ pub async fn mcts_test() {
 
 
 pub fn random_game_test() {
-    let v_w: Vec<_> = vec![0; 12].iter().enumerate()
-        .map(|(i, x)| if i / 4 % 2 == 0 { 2 * i } else { 2 * i + 1 }).collect();
-    let v_b: Vec<_> = vec![0; 12].iter().enumerate()
-        .map(|(i, x)| 63 - if i / 4 % 2 == 0 { 2 * i } else { 2 * i + 1 }).collect();
     let mut game_count = 0;
     loop {
-        let mut game = Game::new(8);
-        game.current_position.next_move = Some(Color::White);
-        v_w.iter()
-            .for_each(|pos|
-                game.insert_piece(Piece::new(game.to_pack(*pos), Color::White, false)));
-        v_b.iter()
-            .for_each(|pos|
-                game.insert_piece(Piece::new(game.to_pack(*pos), Color::Black, false)));
+        let mut game = Game::new_standard(Variant::Russian);
         while game.position_history.borrow_mut().finish_check().is_none() {
             // print!("state {}\n", game.state_());
             // print!("history {:?}\n", game.position_history.len());
@@ -252,6 +236,37 @@ pub fn random_game_test() {
     }
 }
 
+const VARIANTS: [Variant; 10] = [Variant::Russian, Variant::International, Variant::English,
+    Variant::Brazilian, Variant::Pool, Variant::Italian, Variant::Turkish, Variant::Frisian,
+    Variant::Antidraughts, Variant::Canadian];
+
+/// `perft <depth> [variant] [fen] [divide]`: counts the move paths from the start position
+/// of the variant (Russian by default) or from the FEN position, by the first move with `divide`.
+pub fn perft(args: &[String]) {
+    let usage = "perft <depth> [variant] [fen] [divide]";
+    let depth: usize = args.first().and_then(|x| x.parse().ok()).expect(usage);
+    let mut game = Game::new_standard(Variant::Russian);
+    let divide = args[1..].iter().any(|x| x == "divide");
+    for arg in args[1..].iter().filter(|x| *x != "divide") {
+        match VARIANTS.iter().find(|x| format!("{:?}", x).eq_ignore_ascii_case(arg)) {
+            Some(variant) => game = Game::new_standard(*variant),
+            None => game.set_fen(arg).unwrap_or_else(|err| panic!("{}: {}", usage, err)),
+        }
+    }
+    let start = Instant::now();
+    let pos = &mut game.current_position;
+    let nodes = if divide {
+        let environment = pos.environment.clone();
+        let list = pos.divide(depth);
+        for (mov, nodes) in &list {
+            println!("{} {}", mov.notation(&environment, environment.default_notation()), nodes);
+        }
+        list.iter().map(|x| x.1).sum()
+    } else { pos.perft(depth) };
+    let seconds = start.elapsed().as_secs_f64();
+    println!("perft({}) = {} in {:.3} s, {:.0} nodes/s", depth, nodes, seconds, nodes as f64 / seconds);
+}
+
 #[tokio::main]
 pub async fn main() {
     let arg = std::env::args().collect::<Vec<_>>();
@@ -261,6 +276,10 @@ pub async fn main() {
     let mut pass_q: usize = 200_000;
     let mut item_update_every = 100;
     println!("{:?}", arg);
+    if let Some(i) = arg.iter().position(|x| *x == "perft") {
+        perft(&arg[i + 1..]);
+        return;
+    }
     let score: ThreadScore = Arc::new(Mutex::new(Score { d: 0, m: 0, z: 0 }));
 
     let pos = arg.iter().position(|x| *x == "+++".to_string());
@@ -289,16 +308,13 @@ pub async fn main() {
     for _ in 0..threads_q {
         let cache = cache_db.clone();
         let score = score.clone();
-        let x = tokio::task::spawn_blocking(move || {
-            tokio::runtime::Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .unwrap()
-                .block_on(async {
-                    cache.0.write().unwrap().as_mut().unwrap().init_database().await;
-                    deep_mcts(cache, pass_q as i32, depth as i16, score).await
-                })
-        });
+        // the cache keeps std locks across awaits, so a worker has a thread of its own
+        // and drives its future there on the runtime of main
+        let runtime = tokio::runtime::Handle::current();
+        let x = tokio::task::spawn_blocking(move || runtime.block_on(async {
+            cache.0.write().unwrap().as_mut().unwrap().init_database().await;
+            deep_mcts(cache, pass_q as i32, depth as i16, score).await
+        }));
         xx.push(x);
     }
     for x in xx {
@@ -1,9 +1,8 @@
-use std::cell::{Cell, RefCell};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufWriter, Write};
-use std::rc::Rc;
+use crate::shared::Shared;
 use crate::position::{Position, TuplePositionKey};
 use crate::PositionHistory::{FinishType, PositionAndMove, PositionHistory};
 use rand::{Rng};
@@ -80,8 +79,12 @@ pub struct Node {
     pub average_game_len: f64,
     pub finish: Option<FinishType>,
     pub passed: bool,
-    pub(crate) pos_mov: Rc<RefCell<PositionAndMove>>,
-    pub childs: HashMap<VectorPosition, Rc<RefCell<Node>>>,
+    pub(crate) pos_mov: Shared<PositionAndMove>,
+    /// by the Zobrist hash of their positions
+    pub childs: HashMap<u64, Shared<Node>>,
+    /// quiescence value of the position, computed once by `leaf_eval`
+    #[serde(skip)]
+    pub q_eval: Option<i32>,
 }
 
 impl Node {
@@ -92,21 +95,31 @@ impl Node {
             average_game_len: 0.0,
             finish: None,
             passed: false,
-            pos_mov: Rc::new(RefCell::new(pos_mov)),
+            pos_mov: Shared::new(pos_mov),
             childs: HashMap::new(),
+            q_eval: None,
         }
     }
 
-    pub fn get_key(&mut self) -> VectorPosition {
-        self.pos_mov.borrow_mut().pos.get_key()
+    /// Value of the position once the captures are over, White's advantage positive.
+    pub fn leaf_eval(&mut self) -> i32 {
+        if self.q_eval.is_none() {
+            // on a copy, the position keeps the random move order the playouts expand by
+            let mut pos = self.pos_mov.borrow().pos.clone();
+            self.q_eval = Some(pos.quiescence(i32::MIN / 2, i32::MAX / 2, true));
+        }
+        self.q_eval.unwrap()
+    }
+
+    pub fn get_key(&self) -> u64 {
+        self.pos_mov.borrow().pos.hash()
     }
     pub fn childs_iter(&self) {}
     pub fn expand(&mut self) {
         let mut base_p = self.pos_mov.borrow().pos.clone();
         let move_list = base_p.get_move_list_cached();
         for mov in &move_list.as_ref().as_ref().unwrap().list {
-            let node = Rc::new(
-                RefCell::new(Node::new(base_p.make_move_and_get_position(mov))));
+            let node = Shared::new(Node::new(base_p.make_move_and_get_position(mov)));
             if {
                 let child = self.childs.get(&node.borrow_mut().get_key());
                 child.is_none() ||
@@ -120,7 +133,7 @@ impl Node {
     pub fn get_move(&self) -> Option<MoveItem> {
         self.pos_mov.borrow().mov.clone()
     }
-    pub fn get_pos_mov(&self) -> Rc<RefCell<PositionAndMove>> {
+    pub fn get_pos_mov(&self) -> Shared<PositionAndMove> {
         self.pos_mov.clone()
     }
 }
@@ -143,24 +156,27 @@ impl NeuralVecList {
 
 #[derive(Serialize, Debug, Clone, Deserialize)]
 pub struct NodeCacheItem {
-    key: VectorPosition,
+    // Zobrist hash, stored as i64 since bson has no unsigned integers
+    key: i64,
+    cells: VectorPosition,
     quality: Quality,
-    childs: Vec<(VectorPosition, Quality)>,
+    childs: Vec<(i64, VectorPosition, Quality)>,
 }
 
 impl NodeCacheItem {
-    pub fn key(&self) -> VectorPosition {
-        self.key.clone()
+    pub fn key(&self) -> i64 {
+        self.key
     }
 
     pub fn from_node(node: &mut Node) -> NodeCacheItem {
         NodeCacheItem {
-            key: node.get_key(),
+            key: node.get_key() as i64,
+            cells: node.pos_mov.borrow_mut().pos.get_key(),
             quality: Quality { N: node.N, W: node.W },
-            childs: Vec::from_iter(node.childs.iter().map(|(_, x)| {
-                let key = x.borrow_mut().get_key();
+            childs: Vec::from_iter(node.childs.iter().map(|(key, x)| {
+                let cells = x.borrow().pos_mov.borrow_mut().pos.get_key();
                 let y = x.borrow();
-                (key, Quality { N: y.N, W: y.W })
+                (*key as i64, cells, Quality { N: y.N, W: y.W })
             })),
         }
     }
@@ -168,11 +184,11 @@ impl NodeCacheItem {
     pub fn to_vector_list(&self) -> NeuralVecList {
         let mut v = vec![];
         let mut res = NeuralVecList(vec![]);
-        v = self.key.0.iter().map(|x| f32::trunc(*x as f32 / 3.0 * 10.0) / 10.0).collect::<Vec<_>>();
+        v = self.cells.0.iter().map(|x| f32::trunc(*x as f32 / 3.0 * 10.0) / 10.0).collect::<Vec<_>>();
         let next_move = f32::trunc(v.pop().unwrap() * 4.0);
         if next_move < 0.0 { v.reverse() }
-        let NN = self.childs.iter().fold(0, |_, x| x.1.N);
-        for (ve, q) in &self.childs {
+        let NN = self.childs.iter().fold(0, |_, x| x.2.N);
+        for (_, ve, q) in &self.childs {
             let mut v1 = ve.0.iter().map(|x| f32::trunc(*x as f32 / 3.0 * 10.0) / 10.0).collect::<Vec<_>>();
             let mut v = v.clone();
             if v1.pop().unwrap() < 0.0 { v1.reverse() }
@@ -195,7 +211,7 @@ struct Quality {
 }
 
 #[derive(Clone, Default)]
-pub struct Cache(pub Arc<RwLock<Option<CacheDb<VectorPosition, NodeCacheItem>>>>);
+pub struct Cache(pub Arc<RwLock<Option<CacheDb<i64, NodeCacheItem>>>>);
 
 impl Cache {
     pub fn to_file(&self, f_name: String) -> std::io::Result<()> {
@@ -221,25 +237,29 @@ impl Cache {
 //     }
 // }
 
+// quiescence value of a position where one side is left without pieces or moves
+const DECISIVE_EVAL: i32 = i32::MAX / 8;
+
 pub struct McTree {
-    pub root: Rc<RefCell<Node>>,
-    history: Rc<RefCell<PositionHistory>>,
+    pub root: Shared<Node>,
+    history: Shared<PositionHistory>,
     pub cache: Cache,
 }
 
 
 impl McTree {
-    pub fn new(pos: Position, history: Rc<RefCell<PositionHistory>>) -> McTree {
+    pub fn new(pos: Position, history: Shared<PositionHistory>) -> McTree {
         McTree {
-            root: Rc::new(RefCell::new(Node {
+            root: Shared::new(Node {
                 W: 0,
                 N: 0,
                 average_game_len: 0.0,
                 finish: None,
                 passed: false,
-                pos_mov: Rc::new(RefCell::new(PositionAndMove::from_pos(pos))),
+                pos_mov: Shared::new(PositionAndMove::from_pos(pos)),
                 childs: HashMap::new(),
-            })),
+                q_eval: None,
+            }),
             history,
             cache:
             Cache(Arc::new(RwLock::new(
@@ -252,7 +272,7 @@ impl McTree {
     }
 
 
-    pub fn new_from_node(root: Rc<RefCell<Node>>, history: Rc<RefCell<PositionHistory>>, cache: Cache) -> McTree {
+    pub fn new_from_node(root: Shared<Node>, history: Shared<PositionHistory>, cache: Cache) -> McTree {
         McTree {
             root,
             history,
@@ -260,19 +280,20 @@ impl McTree {
         }
     }
 
-    fn root_search(&self, node: &Rc<RefCell<Node>>, mut max_deps: i16, deps: i16) -> Rc<RefCell<Node>> {
+    fn root_search(&self, node: &Shared<Node>, mut max_deps: i16, deps: i16) -> Shared<Node> {
         let color = node.borrow().pos_mov.borrow().pos.next_move.unwrap();
         fn min_max_fn<T, F>(v: &[T], fun: F, color: Color) -> Option<&T>
             where
                 F: FnMut(&&T, &&T) -> Ordering
         {
-            if color == White { Iterator::max_by(v.iter(), fun) } else { Iterator::max_by(v.iter(), fun) }
+            if color == White { Iterator::max_by(v.iter(), fun) } else { Iterator::min_by(v.iter(), fun) }
         }
 
-        fn vec_pos_move_min_max(l: &[Rc<RefCell<Node>>], color: Color) -> Rc<RefCell<Node>> {
-            min_max_fn(l, |x: &&Rc<RefCell<Node>>, y: &&Rc<RefCell<Node>>|
-                x.borrow().pos_mov.borrow().pos.state.cmp(
-                    &y.borrow().pos_mov.borrow().pos.state), color).unwrap().clone()
+        fn vec_pos_move_min_max(l: &[Shared<Node>], color: Color) -> Shared<Node> {
+            min_max_fn(l, |x: &&Shared<Node>, y: &&Shared<Node>| {
+                let x = x.borrow_mut().leaf_eval();
+                x.cmp(&y.borrow_mut().leaf_eval())
+            }, color).unwrap().clone()
         }
 
         if node.borrow().childs.len() == 0 {
@@ -293,7 +314,7 @@ impl McTree {
                 let node0 = vec_pos_move_min_max(list1, color);
                 let mut ind = 0;
                 for node in list1 {
-                    if node.as_ref().as_ptr() == node0.as_ref().as_ptr() { break; }
+                    if Shared::ptr_eq(node, &node0) { break; }
                     ind += 1;
                 }
                 // println!("{:?} ", &list[ind].borrow().pos_mov.borrow().mov);
@@ -304,12 +325,12 @@ impl McTree {
         }
     }
 
-    pub async fn search(&mut self, max_passes: i32) -> Rc<RefCell<Node>> {
+    pub async fn search(&mut self, max_passes: i32) -> Shared<Node> {
         let mut cached_passes = 0;
-        let mut track: Vec<Rc<RefCell<Node>>> = vec![];
+        let mut track: Vec<Shared<Node>> = vec![];
         let hist_len = self.history.borrow().len();
-        let back_propagation = |mut res: i64, track: &mut Vec<Rc<RefCell<Node>>>,
-                                history: &Rc<RefCell<PositionHistory>>,
+        let back_propagation = |mut res: i64, track: &mut Vec<Shared<Node>>,
+                                history: &Shared<PositionHistory>,
                                 hist_len: usize, cache: &Cache| {
             let mut g_len = 0.0;
             let mut depth = track.len();
@@ -331,7 +352,7 @@ impl McTree {
             *track = vec![];
         };
         let mut pass = 0;
-        let u = |N: i64, node: &Rc<RefCell<Node>>|
+        let u = |N: i64, node: &Shared<Node>|
             {
                 // let n = node.borrow().childs.iter()
                 //     .fold(0, |acc, x| acc + x.borrow().N) as f64;
@@ -345,24 +366,24 @@ impl McTree {
                 //     // node.borrow().childs.iter().fold(0, |acc, x|acc+x.borrow().N) as f64
                 //     node.borrow().N as f64
                 // ) / (N as f64 + 1.0);
-                // let u = |N: i64, node: &Rc<RefCell<Node>>|
+                // let u = |N: i64, node: &Shared<Node>|
                 //     10.0 * f64::sqrt(node.borrow().N as f64) / (N as f64 + 1.0);
             };
-        let u_max = |child: &Node, node: &Rc<RefCell<Node>>| {
+        let u_max = |child: &Node, node: &Shared<Node>| {
             child.W as f64 / (child.N as f64 + 1.0) + u(child.N, node)
         };
-        let u_min = |child: &Node, node: &Rc<RefCell<Node>>| {
+        let u_min = |child: &Node, node: &Shared<Node>| {
             // child.N as f64
             child.W as f64 / (child.N as f64 + 1.0) - u(child.N, node)
         };
-        let w_n = |a: &Rc<RefCell<Node>>| a.borrow().W as f64 / (1.0 + a.borrow().N as f64);
+        let w_n = |a: &Shared<Node>| a.borrow().W as f64 / (1.0 + a.borrow().N as f64);
 
-        let mut update_from_cache = |node: &mut Rc<RefCell<Node>>| {
+        let mut update_from_cache = |node: &mut Shared<Node>| {
             // let cache_item = CacheItem::from_node(&mut *node.borrow_mut());
             // let key = cache_item.key();
             let mut ok = false;
             let cache = self.cache.0.read().unwrap();
-            let item_val = cache.as_ref().unwrap().get(&node.borrow_mut().get_key());
+            let item_val = cache.as_ref().unwrap().get(&(node.borrow().get_key() as i64));
             if let Some(item) = &item_val {
                 cached_passes += 1;
                 let it = item.read().unwrap();
@@ -371,9 +392,9 @@ impl McTree {
                     node.borrow_mut().N = it.quality.N;
                     node.borrow_mut().W = it.quality.W;
                     for x in &it.childs {
-                        if let Some(child) = node.borrow_mut().childs.get_mut(&x.0) {
-                            child.borrow_mut().N = x.1.N;
-                            child.borrow_mut().W = x.1.W;
+                        if let Some(child) = node.borrow_mut().childs.get_mut(&(x.0 as u64)) {
+                            child.borrow_mut().N = x.2.N;
+                            child.borrow_mut().W = x.2.W;
                         }
                     }
                 }
@@ -407,7 +428,7 @@ impl McTree {
 
                 if node.borrow().N > 100 {
                     let item =
-                        self.cache.0.read().unwrap().as_ref().unwrap().get(&node.borrow_mut().get_key());
+                        self.cache.0.read().unwrap().as_ref().unwrap().get(&(node.borrow().get_key() as i64));
                     if item.is_none() || node.borrow().N - item.unwrap().read().unwrap().quality.N > 1 {
                         let cache_item = NodeCacheItem::from_node(&mut *node.borrow_mut());
                         self.cache.0.read().unwrap().as_ref().unwrap().insert(cache_item).await;
@@ -421,9 +442,8 @@ impl McTree {
                     if node.borrow().childs.len() < move_list.as_ref().as_ref().unwrap().list.len() {
                         let i = node.borrow().childs.len();
                         let x = &move_list.as_ref().as_ref().unwrap().list[i];
-                        let child = Rc::new(
-                            RefCell::new(Node::new(
-                                node.borrow().pos_mov.borrow_mut().pos.make_move_and_get_position(x))));
+                        let child = Shared::new(Node::new(
+                                node.borrow().pos_mov.borrow_mut().pos.make_move_and_get_position(x)));
                         node.borrow_mut().childs.insert(child.borrow_mut().get_key(), child.clone());
                         node.borrow().pos_mov.borrow_mut().pos.unmake_move(x);
                         child
@@ -485,6 +505,15 @@ impl McTree {
                                      }, &mut track, &self.history, hist_len, &self.cache);
                     break;
                 }
+                // the captures left decide the game, the playout ends at the leaf
+                let q_eval = node.borrow_mut().leaf_eval();
+                if q_eval.abs() >= DECISIVE_EVAL {
+                    node.borrow_mut().N += 1;
+                    let fr = if q_eval > 0 { 1 } else { -1 };
+                    let first = if node.borrow().pos_mov.borrow().pos.next_move == Some(White) { -1 } else { 1 };
+                    back_propagation(fr * first, &mut track, &self.history, hist_len, &self.cache);
+                    break;
+                }
             }
         }
         // println!("cached: {}", cached_passes);
@@ -529,7 +558,47 @@ impl McTree {
         self.root.borrow().childs.iter().map(|(_, x)| x.borrow().N).collect::<Vec<_>>()
     }
 
-    pub fn tree_childs(&self) -> Vec<Rc<RefCell<Node>>> {
+    pub fn tree_childs(&self) -> Vec<Shared<Node>> {
         self.root.borrow().childs.values().map(|x| x.clone()).collect::<Vec<_>>()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+    use crate::cache_db::CacheDb;
+    use crate::game::Game;
+    use crate::mcts::{Cache, McTree, NodeCacheItem, DECISIVE_EVAL};
+    use crate::rules::Variant;
+
+    #[test]
+    fn black_picks_its_best_line() {
+        let mut game = Game::new_variant(Variant::Russian);
+        // 14x23x32 takes two men and crowns, 14x21 takes one
+        game.set_fen("B:W17,18,27:B14").unwrap();
+        let tree = McTree::new(game.current_position.clone(), game.position_history.clone());
+        tree.root.borrow_mut().expand();
+        assert_eq!(tree.root.borrow().childs.len(), 2);
+        let best = tree.root_search(&tree.root, 1, 0).borrow().get_move().unwrap();
+        assert_eq!(best.strike.unwrap().vec.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn playouts_end_at_decided_captures() {
+        let mut game = Game::new_variant(Variant::Russian);
+        // every move of the black man leaves it to the king
+        game.set_fen("B:WK22:B14").unwrap();
+        let mut tree = McTree::new(game.current_position.clone(), game.position_history.clone());
+        let db = CacheDb::new(NodeCacheItem::key, "test".to_string(), "nodes".to_string(), 100, 10, 10).await;
+        tree.set_cache(Cache(Arc::new(RwLock::new(Some(db)))));
+        tree.search(20).await;
+        let childs = tree.tree_childs();
+        assert_eq!(childs.len(), 2);
+        for child in childs {
+            let child = child.borrow();
+            assert!(child.q_eval.is_some_and(|eval| eval >= DECISIVE_EVAL));
+            // the capture is not played out, Black lost every playout through the move
+            assert!(child.childs.is_empty() && child.finish.is_none());
+            assert!(child.N > 1 && child.W < 0, "{} {}", child.N, child.W);
+        }
+    }
+}
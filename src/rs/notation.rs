@@ -0,0 +1,233 @@
+use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use wasm_bindgen::prelude::wasm_bindgen;
+use crate::moves::BoardPos;
+use crate::moves_list::MoveItem;
+use crate::position::Position;
+use crate::position_environment::PositionEnvironment;
+use crate::rules::Variant;
+
+/// How squares are written: by name (`c3`) or by number (`22`).
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum Notation {
+    Algebraic,
+    Numeric,
+}
+
+/// Why a move string could not be turned into a move of the position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    /// not squares divided by `-` or `x`
+    Format(String),
+    /// the text names no playable square of the board
+    Square(String),
+    /// no legal move goes this way
+    Illegal(String),
+    /// several legal moves taking different pieces go this way, written in full
+    Ambiguous(String, Vec<String>),
+}
+
+impl Display for NotationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotationError::Format(text) =>
+                write!(f, "\"{}\" must look like c3-d4, 22-18 or 22x13x6", text),
+            NotationError::Square(square) =>
+                write!(f, "\"{}\" is not a playable square of the board", square),
+            NotationError::Illegal(text) =>
+                write!(f, "{} is not a legal move", text),
+            NotationError::Ambiguous(text, moves) =>
+                write!(f, "{} is ambiguous, it can be {}", text, moves.join(" or ")),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+impl PositionEnvironment {
+    /// Russian draughts name the squares, the other variants number them.
+    pub fn default_notation(&self) -> Notation {
+        match self.variant {
+            None | Some(Variant::Russian) | Some(Variant::Antidraughts) => Notation::Algebraic,
+            _ => Notation::Numeric,
+        }
+    }
+
+    /// Name (`a1` is the lower left corner from White's view) or number of the square.
+    pub fn square_name(&self, pos: BoardPos, notation: Notation) -> String {
+        match notation {
+            Notation::Algebraic => {
+                let board = self.pack_to_board[pos];
+                let size = self.size as usize;
+                format!("{}{}", (b'a' + (board % size) as u8) as char, board / size + 1)
+            }
+            Notation::Numeric => self.pack_to_square(pos).to_string(),
+        }
+    }
+
+    /// Square of a name or of a number.
+    pub fn parse_square(&self, text: &str) -> Option<BoardPos> {
+        if text.starts_with(|c: char| c.is_ascii_digit()) {
+            return self.square_to_pack(text.parse().ok()?);
+        }
+        let size = self.size as usize;
+        let col = (text.chars().next()?.to_ascii_lowercase() as usize).checked_sub('a' as usize)?;
+        let row = text.get(1..)?.parse::<usize>().ok()?.checked_sub(1)?;
+        if col >= size || row >= size { return None; }
+        let board = row * size + col;
+        let pos = self.board_to_pack[board];
+        if self.pack_to_board.get(pos) == Some(&board) { Some(pos) } else { None }
+    }
+}
+
+impl MoveItem {
+    /// Squares the piece stands on: the start and every landing square.
+    pub fn path(&self) -> Vec<BoardPos> {
+        match &self.strike {
+            Some(strike) => std::iter::once(self.from()).chain(strike.vec.iter().map(|x| x.to)).collect(),
+            None => vec![self.from(), self.to()],
+        }
+    }
+
+    /// Taken pieces in ascending order.
+    pub fn taken(&self) -> Vec<BoardPos> {
+        let mut taken: Vec<_> = self.strike.iter().flat_map(|x| x.vec.iter().map(|x| x.take)).collect();
+        taken.sort();
+        taken
+    }
+
+    /// Canonical text of the move: `c3-d4`, or every landing square of a capture, `22x13x6`.
+    pub fn notation(&self, environment: &PositionEnvironment, notation: Notation) -> String {
+        self.path().iter().map(|x| environment.square_name(*x, notation)).collect::<Vec<_>>()
+            .join(if self.strike.is_some() { "x" } else { "-" })
+    }
+}
+
+impl Position {
+    /// Shortest text of the move: start and end squares, the whole path if they don't identify it.
+    pub fn move_notation(&mut self, mov: &MoveItem, notation: Notation) -> String {
+        let list = self.get_move_list(false);
+        let ambiguous = list.list.iter()
+            .any(|x| x.from() == mov.from() && x.to() == mov.to() && x.taken() != mov.taken());
+        let text = mov.notation(&self.environment, notation);
+        if ambiguous { return text; }
+        let separator = if mov.strike.is_some() { "x" } else { "-" };
+        let squares: Vec<_> = text.split(separator).collect();
+        format!("{}{}{}", squares[0], separator, squares[squares.len() - 1])
+    }
+
+    /// Legal move written as `c3-d4`, `22x13x6` or `22x6`; numbers and names may be mixed.
+    /// With only the start and end squares, captures taking the same pieces are one move.
+    pub fn parse_move(&mut self, text: &str) -> Result<MoveItem, NotationError> {
+        let text = text.trim();
+        let capture = text.contains(['x', 'X', ':']);
+        let squares = text.split(['-', 'x', 'X', ':']).map(|x| {
+            if x.is_empty() { return Err(NotationError::Format(text.to_string())); }
+            self.environment.parse_square(x).ok_or_else(|| NotationError::Square(x.to_string()))
+        }).collect::<Result<Vec<_>, _>>()?;
+        if squares.len() < 2 || (!capture && squares.len() > 2) {
+            return Err(NotationError::Format(text.to_string()));
+        }
+        let list = self.get_move_list(false);
+        let found: Vec<_> = list.list.into_iter().filter(|x| {
+            if x.strike.is_some() != capture { return false; }
+            let path = x.path();
+            if squares.len() > 2 { path == squares } else {
+                path[0] == squares[0] && path.last() == squares.last()
+            }
+        }).collect();
+        match found.first() {
+            None => Err(NotationError::Illegal(text.to_string())),
+            Some(first) if found.iter().any(|x| x.taken() != first.taken()) => {
+                let mut moves: Vec<_> = found.iter()
+                    .map(|x| x.notation(&self.environment, self.environment.default_notation())).collect();
+                moves.sort();
+                moves.dedup();
+                Err(NotationError::Ambiguous(text.to_string(), moves))
+            }
+            Some(first) => Ok(first.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Game;
+    use crate::notation::{Notation, NotationError};
+    use crate::rules::Variant;
+
+    #[test]
+    fn square_names() {
+        for variant in [Variant::Russian, Variant::International, Variant::Italian, Variant::Turkish, Variant::Canadian] {
+            let environment = &Game::new_variant(variant).current_position.environment;
+            for pos in 0..environment.squares() {
+                for notation in [Notation::Algebraic, Notation::Numeric] {
+                    let name = environment.square_name(pos, notation);
+                    assert_eq!(environment.parse_square(&name), Some(pos), "{:?} {}", variant, name);
+                }
+            }
+        }
+        let environment = &Game::new(8).current_position.environment;
+        assert_eq!(environment.square_name(0, Notation::Algebraic), "a1");
+        assert_eq!(environment.parse_square("b1"), None);
+        assert_eq!(environment.parse_square("i2"), None);
+        assert_eq!(environment.parse_square("33"), None);
+        let environment = &Game::new_variant(Variant::Canadian).current_position.environment;
+        assert_eq!(environment.parse_square("l12").map(|x| environment.pack_to_board[x]), Some(143));
+    }
+
+    #[test]
+    fn move_text() {
+        let mut game = Game::new_standard(Variant::Russian);
+        let pos = &mut game.current_position;
+        let mov = pos.parse_move("c3-d4").unwrap();
+        assert_eq!(pos.parse_move("22-18"), Ok(mov.clone()));
+        assert_eq!(mov.notation(&pos.environment, Notation::Numeric), "22-18");
+        assert_eq!(pos.move_notation(&mov, Notation::Algebraic), "c3-d4");
+        for mov in pos.get_move_list(false).list {
+            for notation in [Notation::Algebraic, Notation::Numeric] {
+                assert_eq!(pos.parse_move(&mov.notation(&pos.environment, notation)), Ok(mov.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn strike_text() {
+        let mut game = Game::new(8);
+        game.set_fen("W:W22:B18,10").unwrap();
+        let pos = &mut game.current_position;
+        let mov = pos.parse_move("c3xe5xc7").unwrap();
+        assert_eq!(mov.notation(&pos.environment, Notation::Numeric), "22x15x6");
+        assert_eq!(pos.parse_move("22x6"), Ok(mov.clone()));
+        assert_eq!(pos.move_notation(&mov, Notation::Numeric), "22x6");
+    }
+
+    #[test]
+    fn notation_errors() {
+        let mut game = Game::new_standard(Variant::Russian);
+        let pos = &mut game.current_position;
+        assert_eq!(pos.parse_move("c3"), Err(NotationError::Format("c3".to_string())));
+        assert_eq!(pos.parse_move("c3--d4"), Err(NotationError::Format("c3--d4".to_string())));
+        assert_eq!(pos.parse_move("c3-d9"), Err(NotationError::Square("d9".to_string())));
+        assert_eq!(pos.parse_move("c3-c4"), Err(NotationError::Square("c4".to_string())));
+        assert_eq!(pos.parse_move("c3-e5"), Err(NotationError::Illegal("c3-e5".to_string())));
+        assert_eq!(pos.parse_move("c3xd4"), Err(NotationError::Illegal("c3xd4".to_string())));
+        // the king on a1 from the readme: several captures from a1 end on the same square
+        game.set_fen("W:WK29:B9,10,11,17,18,19,25,26,27").unwrap();
+        let pos = &mut game.current_position;
+        let list = pos.get_move_list(false).list;
+        let mov = list.iter().find(|x| pos.move_notation(x, Notation::Algebraic).matches('x').count() > 1).unwrap();
+        let full = mov.notation(&pos.environment, Notation::Algebraic);
+        let short = format!("a1x{}", &full[full.len() - 2..]);
+        match pos.parse_move(&short) {
+            Err(NotationError::Ambiguous(text, moves)) => {
+                assert_eq!(text, short);
+                assert!(moves.len() > 1 && moves.contains(&full));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,679 @@
+use std::fmt::{Display, Formatter};
+use crate::color::Color;
+use crate::fen::FenError;
+use crate::game::Game;
+use crate::game_tree::{GameTree, NodeId};
+use crate::moves_list::MoveItem;
+use crate::position::Position;
+use crate::notation::NotationError;
+use crate::PositionHistory::FinishType;
+use crate::rules::Variant;
+
+/// Why a PDN text could not be read or replayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PdnError {
+    /// broken tag, comment or variation
+    Syntax(String),
+    /// unknown number in the GameType tag
+    GameType(String),
+    Fen(FenError),
+    /// the move (ply from 1, text) is not legal in its position
+    IllegalMove(usize, String),
+    /// the move (ply from 1, text) matches captures taking different pieces
+    AmbiguousMove(usize, String),
+}
+
+impl Display for PdnError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PdnError::Syntax(message) => write!(f, "PDN syntax: {}", message),
+            PdnError::GameType(game_type) => write!(f, "unsupported GameType \"{}\"", game_type),
+            PdnError::Fen(err) => write!(f, "FEN tag: {}", err),
+            PdnError::IllegalMove(ply, mov) => write!(f, "illegal move {} at ply {}", mov, ply),
+            PdnError::AmbiguousMove(ply, mov) =>
+                write!(f, "ambiguous move {} at ply {}, give the squares it passes", mov, ply),
+        }
+    }
+}
+
+impl std::error::Error for PdnError {}
+
+impl From<FenError> for PdnError {
+    fn from(err: FenError) -> Self {
+        PdnError::Fen(err)
+    }
+}
+
+/// A move of the movetext as written: squares by number (`22`) or by name (`c3`).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PdnMove {
+    pub squares: Vec<String>,
+    pub capture: bool,
+    pub nags: Vec<u32>,
+    /// comment after the move
+    pub comment: Option<String>,
+    /// alternatives to this move
+    pub variations: Vec<PdnLine>,
+}
+
+/// Moves of the main line or of a variation, with the comment before them.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PdnLine {
+    pub comment: Option<String>,
+    pub moves: Vec<PdnMove>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PdnGame {
+    pub tags: Vec<(String, String)>,
+    pub line: PdnLine,
+    pub result: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Open,
+    Close,
+    Nag(u32),
+    Move(PdnMove),
+    Result(String),
+}
+
+const RESULTS: [&str; 8] = ["2-0", "0-2", "1-1", "1-0", "0-1", "1/2-1/2", "0-0", "*"];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PdnError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    let until = |i: usize, end: char, what: &str| -> Result<(String, usize), PdnError> {
+        let len = chars[i..].iter().position(|c| *c == end)
+            .ok_or_else(|| PdnError::Syntax(format!("unclosed {}", what)))?;
+        Ok((chars[i..i + len].iter().collect(), i + len + 1))
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '[' {
+            let (tag, next) = until(i + 1, ']', "tag")?;
+            let (name, value) = tag.trim().split_once(char::is_whitespace)
+                .ok_or_else(|| PdnError::Syntax(format!("tag [{}] has no value", tag)))?;
+            let value = value.trim();
+            if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+                return Err(PdnError::Syntax(format!("value of tag {} must be quoted", name)));
+            }
+            let value = value[1..value.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\");
+            tokens.push(Token::Tag(name.to_string(), value));
+            i = next;
+        } else if c == '{' {
+            let (comment, next) = until(i + 1, '}', "comment")?;
+            tokens.push(Token::Comment(comment.trim().to_string()));
+            i = next;
+        } else if c == ';' {
+            let len = chars[i..].iter().position(|c| *c == '\n').unwrap_or(chars.len() - i);
+            tokens.push(Token::Comment(chars[i + 1..i + len].iter().collect::<String>().trim().to_string()));
+            i += len;
+        } else if c == '(' {
+            tokens.push(Token::Open);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::Close);
+            i += 1;
+        } else {
+            let len = chars[i..].iter()
+                .position(|c| c.is_whitespace() || "{}()[];".contains(*c)).unwrap_or(chars.len() - i);
+            let word: String = chars[i..i + len].iter().collect();
+            i += len;
+            word_tokens(&word, &mut tokens)?;
+        }
+    }
+    Ok(tokens)
+}
+
+fn word_tokens(word: &str, tokens: &mut Vec<Token>) -> Result<(), PdnError> {
+    if RESULTS.contains(&word) {
+        tokens.push(Token::Result(word.to_string()));
+        return Ok(());
+    }
+    if let Some(nag) = word.strip_prefix('$') {
+        let nag = nag.parse().map_err(|_| PdnError::Syntax(format!("bad NAG {}", word)))?;
+        tokens.push(Token::Nag(nag));
+        return Ok(());
+    }
+    // a move number, alone or stuck to the move: "12." "12..." "12.22-17"
+    let word = match word.find('.') {
+        Some(dot) if word[..dot].chars().all(|c| c.is_ascii_digit()) => word[dot..].trim_start_matches('.'),
+        _ => word,
+    };
+    if word.is_empty() { return Ok(()); }
+    let text = word.trim_end_matches(['!', '?']);
+    let nag = match &word[text.len()..] {
+        "" => None,
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        other => return Err(PdnError::Syntax(format!("bad move annotation {}", other))),
+    };
+    let capture = text.contains('x');
+    let squares: Vec<String> = text.split(['-', 'x']).map(str::to_string).collect();
+    if squares.len() < 2 || squares.iter().any(|x| x.is_empty() || !x.chars().all(|c| c.is_ascii_alphanumeric())) {
+        return Err(PdnError::Syntax(format!("\"{}\" is not a move", word)));
+    }
+    tokens.push(Token::Move(PdnMove { squares, capture, nags: nag.into_iter().collect(), ..PdnMove::default() }));
+    Ok(())
+}
+
+fn add_comment(comment: &mut Option<String>, text: String) {
+    *comment = Some(match comment.take() {
+        Some(before) => format!("{} {}", before, text),
+        None => text,
+    });
+}
+
+/// Reads moves up to the end of the variation (`depth` > 0) or of the game.
+fn parse_line(tokens: &[Token], i: &mut usize, depth: usize) -> Result<PdnLine, PdnError> {
+    let mut line = PdnLine::default();
+    while *i < tokens.len() {
+        match &tokens[*i] {
+            Token::Tag(..) | Token::Result(_) if depth == 0 => break,
+            Token::Tag(name, _) => return Err(PdnError::Syntax(format!("tag {} inside a variation", name))),
+            Token::Result(_) => {}
+            Token::Comment(text) => match line.moves.last_mut() {
+                Some(mov) => add_comment(&mut mov.comment, text.clone()),
+                None => add_comment(&mut line.comment, text.clone()),
+            },
+            Token::Nag(nag) => match line.moves.last_mut() {
+                Some(mov) => mov.nags.push(*nag),
+                None => return Err(PdnError::Syntax(format!("${} before any move", nag))),
+            },
+            Token::Move(mov) => line.moves.push(mov.clone()),
+            Token::Open => {
+                *i += 1;
+                let variation = parse_line(tokens, i, depth + 1)?;
+                match line.moves.last_mut() {
+                    Some(mov) => mov.variations.push(variation),
+                    None => return Err(PdnError::Syntax("variation before any move".to_string())),
+                }
+                continue;
+            }
+            Token::Close if depth > 0 => {
+                *i += 1;
+                return Ok(line);
+            }
+            Token::Close => return Err(PdnError::Syntax("unexpected )".to_string())),
+        }
+        *i += 1;
+    }
+    if depth > 0 { return Err(PdnError::Syntax("unclosed variation".to_string())); }
+    Ok(line)
+}
+
+impl PdnGame {
+    /// Reads every game of a PDN file.
+    pub fn parse_all(text: &str) -> Result<Vec<PdnGame>, PdnError> {
+        let tokens = tokenize(text)?;
+        let mut games = vec![];
+        let mut i = 0;
+        while i < tokens.len() {
+            let mut game = PdnGame::default();
+            while let Some(Token::Tag(name, value)) = tokens.get(i) {
+                game.tags.push((name.clone(), value.clone()));
+                i += 1;
+            }
+            game.line = parse_line(&tokens, &mut i, 0)?;
+            if let Some(Token::Result(result)) = tokens.get(i) {
+                game.result = Some(result.clone());
+                i += 1;
+            }
+            games.push(game);
+        }
+        Ok(games)
+    }
+
+    /// Reads the first game of the text.
+    pub fn parse(text: &str) -> Result<PdnGame, PdnError> {
+        Ok(PdnGame::parse_all(text)?.into_iter().next().unwrap_or_default())
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(x, _)| x == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(x, _)| x == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// The variant of the GameType tag, International draughts without it as the standard says.
+    /// Antidraughts has no GameType number of its own: Russian with the Variant tag "Antidraughts".
+    pub fn variant(&self) -> Result<Variant, PdnError> {
+        let variant = match self.tag("GameType") {
+            None => Variant::International,
+            Some(game_type) => {
+                let number = game_type.split(',').next().unwrap_or("").trim();
+                number.parse().ok().and_then(variant_of_game_type)
+                    .ok_or_else(|| PdnError::GameType(game_type.to_string()))?
+            }
+        };
+        match self.tag("Variant") {
+            Some(name) if name.eq_ignore_ascii_case(ANTIDRAUGHTS_TAG) => match variant {
+                Variant::Russian => Ok(Variant::Antidraughts),
+                _ => Err(PdnError::GameType(self.tag("GameType").unwrap_or("").to_string())),
+            },
+            _ => Ok(variant),
+        }
+    }
+
+    /// Tags the game as `variant` if it has no GameType, for the files that leave the tag out
+    /// but play another game than International draughts.
+    pub fn or_variant(mut self, variant: Variant) -> PdnGame {
+        if self.tag("GameType").is_none() { self.set_variant(variant); }
+        self
+    }
+
+    fn set_variant(&mut self, variant: Variant) {
+        self.set_tag("GameType", &game_type(variant).to_string());
+        if variant == Variant::Antidraughts {
+            self.set_tag("Variant", ANTIDRAUGHTS_TAG);
+        }
+    }
+
+    /// All moves with their variations and annotations, from the FEN tag or the standard start.
+    pub fn to_tree(&self) -> Result<GameTree, PdnError> {
+        let mut game = Game::new_standard(self.variant()?);
+        if let Some(fen) = self.tag("FEN") {
+            game.set_fen(fen)?;
+        }
+        let mut tree = GameTree::new(game.current_position);
+        add_line(&mut tree, &self.line)?;
+        tree.go_to(*tree.main_line(tree.root()).last().unwrap());
+        Ok(tree)
+    }
+
+    /// Replays the main line, filling `position_history`.
+    pub fn to_game(&self) -> Result<Game, PdnError> {
+        let tree = self.to_tree()?;
+        let mut game = Game::new_variant(self.variant()?);
+        game.follow(&tree, tree.current);
+        Ok(game)
+    }
+
+    /// The tree's lines with GameType, FEN (if the start is not the standard one) and Result tags.
+    pub fn from_tree(tree: &GameTree) -> PdnGame {
+        let root = tree.node(tree.root()).unwrap();
+        let environment = root.pos.environment.clone();
+        let mut pdn = PdnGame::default();
+        for tag in ["Event", "White", "Black"] {
+            pdn.set_tag(tag, "?");
+        }
+        // an environment made without a variant plays the default Russian rules
+        pdn.set_variant(environment.variant.unwrap_or(Variant::Russian));
+        if root.pos != Position::initial(environment.clone()) {
+            pdn.set_tag("FEN", &root.pos.to_fen());
+        }
+        pdn.line = match root.children.first() {
+            Some(first) => tree_line(tree, *first),
+            None => PdnLine::default(),
+        };
+        pdn.line.comment = annotation(root.comment.as_ref(), root.eval);
+        let main_line = tree.main_line(tree.root());
+        let finish = if main_line.len() == 1 { None } else {
+            tree.history(*main_line.last().unwrap()).finish_check()
+        };
+        let english = environment.variant == Some(Variant::English);
+        let result = match finish {
+            None => "*",
+            Some(FinishType::WhiteWin) => if english { "1-0" } else { "2-0" },
+            Some(FinishType::BlackWin) => if english { "0-1" } else { "0-2" },
+            Some(_) => if english { "1/2-1/2" } else { "1-1" },
+        };
+        pdn.set_tag("Result", result);
+        pdn.result = Some(result.to_string());
+        pdn
+    }
+
+    pub fn from_game(game: &Game) -> PdnGame {
+        PdnGame::from_tree(&GameTree::from_game(game))
+    }
+
+    /// Side that makes the first move of the main line.
+    fn first_mover(&self) -> bool {
+        let first = self.variant().map(|x| x.rules().first_move()).unwrap_or(Color::White);
+        match self.tag("FEN").and_then(|fen| fen.trim().chars().next()) {
+            Some('W') => first == Color::White,
+            Some('B') => first == Color::Black,
+            _ => true,
+        }
+    }
+}
+
+// value of the Variant tag that turns the Russian GameType into antidraughts
+const ANTIDRAUGHTS_TAG: &str = "Antidraughts";
+
+// PDN GameType numbers, antidraughts is played on the Russian board
+fn game_type(variant: Variant) -> u32 {
+    match variant {
+        Variant::International => 20,
+        Variant::English => 21,
+        Variant::Italian => 22,
+        Variant::Pool => 23,
+        Variant::Russian | Variant::Antidraughts => 25,
+        Variant::Brazilian => 26,
+        Variant::Canadian => 27,
+        Variant::Turkish => 30,
+        Variant::Frisian => 40,
+    }
+}
+
+fn variant_of_game_type(number: u32) -> Option<Variant> {
+    [Variant::International, Variant::English, Variant::Italian, Variant::Pool, Variant::Russian,
+        Variant::Brazilian, Variant::Canadian, Variant::Turkish, Variant::Frisian].into_iter()
+        .find(|variant| game_type(*variant) == number)
+}
+
+impl PdnMove {
+    fn text(&self) -> String {
+        self.squares.join(if self.capture { "x" } else { "-" })
+    }
+
+    /// Shortest text of the move in the position, the whole path only when needed.
+    fn from_move(pos: &mut Position, mov: &MoveItem) -> PdnMove {
+        let text = pos.move_notation(mov, pos.environment.default_notation());
+        PdnMove {
+            squares: text.split(['-', 'x']).map(str::to_string).collect(),
+            capture: mov.strike.is_some(),
+            ..PdnMove::default()
+        }
+    }
+
+    fn to_move(&self, pos: &mut Position, ply: usize) -> Result<MoveItem, PdnError> {
+        pos.parse_move(&self.text()).map_err(|err| match err {
+            NotationError::Ambiguous(text, _) => PdnError::AmbiguousMove(ply, text),
+            _ => PdnError::IllegalMove(ply, self.text()),
+        })
+    }
+}
+
+const EVAL: &str = "[%eval ";
+
+/// Comment text with the engine eval in front as `[%eval 35]`.
+fn annotation(comment: Option<&String>, eval: Option<i32>) -> Option<String> {
+    match (eval, comment) {
+        (Some(eval), Some(comment)) => Some(format!("{}{}] {}", EVAL, eval, comment)),
+        (Some(eval), None) => Some(format!("{}{}]", EVAL, eval)),
+        (None, comment) => comment.cloned(),
+    }
+}
+
+/// Splits `[%eval 35]` off the comment.
+fn split_annotation(comment: &Option<String>) -> (Option<String>, Option<i32>) {
+    let Some(comment) = comment else { return (None, None) };
+    let eval = comment.strip_prefix(EVAL).and_then(|rest| rest.split_once(']'))
+        .and_then(|(eval, rest)| Some((eval.trim().parse().ok()?, rest.trim())));
+    match eval {
+        Some((eval, "")) => (None, Some(eval)),
+        Some((eval, rest)) => (Some(rest.to_string()), Some(eval)),
+        None => (Some(comment.clone()), None),
+    }
+}
+
+/// The line starting with the tree node, variations of every move from its siblings.
+fn tree_line(tree: &GameTree, first: NodeId) -> PdnLine {
+    let mut line = PdnLine::default();
+    let mut next = Some(first);
+    while let Some(id) = next {
+        let node = tree.node(id).unwrap();
+        let parent = tree.node(node.parent.unwrap()).unwrap();
+        let mut mov = PdnMove::from_move(&mut parent.pos.clone(), node.mov.as_ref().unwrap());
+        mov.nags = node.nags.clone();
+        mov.comment = annotation(node.comment.as_ref(), node.eval);
+        if parent.children[0] == id {
+            mov.variations = parent.children[1..].iter().map(|x| tree_line(tree, *x)).collect();
+        }
+        line.moves.push(mov);
+        next = node.children.first().copied();
+    }
+    line
+}
+
+/// Adds the line and its variations after the current node of the tree.
+fn add_line(tree: &mut GameTree, line: &PdnLine) -> Result<(), PdnError> {
+    let start = tree.current;
+    let (comment, eval) = split_annotation(&line.comment);
+    if start == tree.root() {
+        let root = tree.node_mut(start).unwrap();
+        root.comment = comment;
+        root.eval = eval;
+    }
+    for mov in &line.moves {
+        let from = tree.current;
+        let ply = tree.path(from).len();
+        let move_item = mov.to_move(&mut tree.node(from).unwrap().pos.clone(), ply)?;
+        let id = tree.add_move(&move_item);
+        let (comment, eval) = split_annotation(&mov.comment);
+        let node = tree.node_mut(id).unwrap();
+        node.nags = mov.nags.clone();
+        node.comment = comment;
+        node.eval = eval;
+        for variation in &mov.variations {
+            tree.go_to(from);
+            add_line(tree, variation)?;
+        }
+        tree.go_to(id);
+    }
+    tree.go_to(start);
+    Ok(())
+}
+
+fn write_line(line: &PdnLine, first_ply: usize, words: &mut Vec<String>) {
+    if let Some(comment) = &line.comment { words.push(format!("{{{}}}", comment)); }
+    let mut need_number = true;
+    for (i, mov) in line.moves.iter().enumerate() {
+        let ply = first_ply + i;
+        if ply.is_multiple_of(2) {
+            words.push(format!("{}.", ply / 2 + 1));
+        } else if need_number {
+            words.push(format!("{}...", ply / 2 + 1));
+        }
+        words.push(mov.text());
+        words.extend(mov.nags.iter().map(|nag| format!("${}", nag)));
+        need_number = false;
+        if let Some(comment) = &mov.comment {
+            words.push(format!("{{{}}}", comment));
+            need_number = true;
+        }
+        for variation in &mov.variations {
+            let mut variation_words = vec![];
+            write_line(variation, ply, &mut variation_words);
+            if let Some(first) = variation_words.first_mut() { first.insert(0, '('); }
+            if let Some(last) = variation_words.last_mut() { last.push(')'); }
+            words.extend(variation_words);
+            need_number = true;
+        }
+    }
+}
+
+impl Display for PdnGame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in &self.tags {
+            writeln!(f, "[{} \"{}\"]", name, value.replace('\\', "\\\\").replace('"', "\\\""))?;
+        }
+        if !self.tags.is_empty() { writeln!(f)?; }
+        let mut words = vec![];
+        write_line(&self.line, if self.first_mover() { 0 } else { 1 }, &mut words);
+        words.push(self.result.clone().unwrap_or("*".to_string()));
+        let mut width = 0;
+        for (i, word) in words.iter().enumerate() {
+            if i > 0 {
+                if width + word.len() >= 80 {
+                    writeln!(f)?;
+                    width = 0;
+                } else {
+                    write!(f, " ")?;
+                    width += 1;
+                }
+            }
+            write!(f, "{}", word)?;
+            width += word.len();
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+    use crate::game::Game;
+    use crate::pdn::{PdnError, PdnGame};
+    use crate::rules::Variant;
+
+    const GAME: &str = r#"[Event "Club match"]
+[White "Ivanov"]
+[Black "Petrov"]
+[GameType "20"]
+[Result "*"]
+
+{opening} 1. 32-28 19-23 {the usual reply} 2. 28x19 14x23 $1
+(2... 13x24 3. 34-30 (3. 33-29) 3... 18-22) 3. 37-32! *
+"#;
+
+    #[test]
+    fn read_movetext() {
+        let pdn = PdnGame::parse(GAME).unwrap();
+        assert_eq!(pdn.tag("White"), Some("Ivanov"));
+        assert_eq!(pdn.variant(), Ok(Variant::International));
+        assert_eq!(pdn.line.comment.as_deref(), Some("opening"));
+        let moves = &pdn.line.moves;
+        assert_eq!(moves.len(), 5);
+        assert_eq!(moves[1].comment.as_deref(), Some("the usual reply"));
+        assert!(moves[2].capture);
+        assert_eq!(moves[3].nags, vec![1]);
+        assert_eq!(moves[4].nags, vec![1]);
+        let variation = &moves[3].variations[0];
+        assert_eq!(variation.moves.len(), 3);
+        assert_eq!(variation.moves[1].variations[0].moves[0].squares, vec!["33", "29"]);
+        assert_eq!(pdn.result.as_deref(), Some("*"));
+        // written and read again it stays the same
+        assert_eq!(PdnGame::parse(&pdn.to_string()).unwrap(), pdn);
+    }
+
+    #[test]
+    fn load_game() {
+        let game = Game::from_pdn(GAME).unwrap();
+        assert_eq!(game.position_history.borrow().len(), 5);
+        assert_eq!(game.current_position.next_move, Some(Color::Black));
+        let state = &game.current_position.state;
+        assert_eq!((state.white.simple, state.black.simple), (19, 19));
+    }
+
+    #[test]
+    fn tree_export() {
+        let mut tree = PdnGame::parse(GAME).unwrap().to_tree().unwrap();
+        let main = tree.main_line(tree.root());
+        assert_eq!(main.len(), 6);
+        let side = tree.node(main[3]).unwrap().children[1];
+        assert_eq!(tree.main_line(side).len(), 3);
+        assert_eq!(tree.node(main[2]).unwrap().comment.as_deref(), Some("the usual reply"));
+        tree.node_mut(main[2]).unwrap().eval = Some(-35);
+        tree.node_mut(side).unwrap().comment = Some("also good".to_string());
+        let text = PdnGame::from_tree(&tree).to_string();
+        let words = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+        assert!(words(&text).contains("19-23 {[%eval -35] the usual reply}"), "{}", text);
+        assert!(words(&text).contains("(2... 13x24 {also good} 3. 34-30 (3. 33-29) 3... 18-22)"), "{}", text);
+        let other = PdnGame::parse(&text).unwrap().to_tree().unwrap();
+        assert_eq!(other.node(main[2]).unwrap().eval, Some(-35));
+        assert_eq!(PdnGame::from_tree(&other).to_string(), text);
+        tree.promote(side);
+        let text = PdnGame::from_tree(&tree).to_string();
+        assert!(words(&text).contains("2. 28x19 13x24 {also good} (2... 14x23 $1 3. 37-32 $1) 3. 34-30"), "{}", text);
+    }
+
+    #[test]
+    fn several_games() {
+        let text = format!("{}\n[FEN \"B:W18,K30:B14\"]\n1... 14x23 0-2\n", GAME);
+        let games = PdnGame::parse_all(&text).unwrap();
+        assert_eq!(games.len(), 2);
+        let game = games[1].clone().or_variant(Variant::Russian).to_game().unwrap();
+        assert_eq!(game.current_position.state.white.simple, 0);
+        let pdn = games[1].to_string();
+        assert!(pdn.contains("1... 14x23"), "{}", pdn);
+    }
+
+    #[test]
+    fn write_and_read() {
+        for variant in [Variant::Russian, Variant::International, Variant::English, Variant::Turkish,
+            Variant::Antidraughts] {
+            let mut game = Game::new_standard(variant);
+            for ply in 0..40 {
+                let list = game.current_position.get_move_list(false);
+                if list.list.is_empty() { break; }
+                if game.make_move_by_move_item(&list.list[ply * 7 % list.list.len()]).is_some() { break; }
+            }
+            let text = game.to_pdn();
+            let other = Game::from_pdn(&text).unwrap();
+            assert_eq!(other.current_position.environment.variant, Some(variant));
+            assert!(other.current_position == game.current_position, "{:?}\n{}", variant, text);
+            let (a, b) = (game.position_history.borrow(), other.position_history.borrow());
+            assert_eq!(a.len(), b.len());
+            assert!(a.list.iter().zip(&b.list).all(|(x, y)| x.borrow().pos == y.borrow().pos));
+        }
+    }
+
+    #[test]
+    fn ambiguous_strikes() {
+        // the king on a1 from the readme has 42 captures, several of them share the start and the end
+        let fen = "W:WK29:B9,10,11,17,18,19,25,26,27";
+        let mut game = Game::new(8);
+        game.set_fen(fen).unwrap();
+        let list = game.current_position.get_move_list(false);
+        assert_eq!(list.list.len(), 42);
+        let mut ambiguous = 0;
+        for mov in &list.list {
+            game.set_fen(fen).unwrap();
+            game.make_move_by_move_item(mov);
+            let pdn = game.to_pdn();
+            let other = Game::from_pdn(&pdn).unwrap();
+            assert!(other.current_position == game.current_position, "{}", pdn);
+            let mut short = PdnGame::parse(&pdn).unwrap();
+            let squares = &mut short.line.moves[0].squares;
+            if squares.len() > 2 {
+                squares.drain(1..squares.len() - 1);
+                assert!(matches!(short.to_game().err(), Some(PdnError::AmbiguousMove(1, _))));
+                ambiguous += 1;
+            }
+        }
+        assert!(ambiguous > 0);
+    }
+
+    #[test]
+    fn missing_game_type() {
+        let pdn = PdnGame::parse("1. 32-28 19-23 *").unwrap();
+        assert_eq!(pdn.variant(), Ok(Variant::International));
+        assert_eq!(pdn.to_game().unwrap().current_position.environment.variant, Some(Variant::International));
+        // the Russian files without the tag are read by choice
+        let pdn = PdnGame::parse("1. c3-d4 h6-g5 *").unwrap().or_variant(Variant::Russian);
+        assert_eq!(pdn.to_game().unwrap().current_position.environment.variant, Some(Variant::Russian));
+        let pdn = PdnGame::parse(GAME).unwrap().or_variant(Variant::Russian);
+        assert_eq!(pdn.variant(), Ok(Variant::International));
+        let pdn = PdnGame::parse("").unwrap().or_variant(Variant::Antidraughts);
+        assert_eq!(pdn.variant(), Ok(Variant::Antidraughts));
+    }
+
+    #[test]
+    fn pdn_errors() {
+        assert_eq!(Game::from_pdn("1. 22-14 *").err(), Some(PdnError::IllegalMove(1, "22-14".to_string())));
+        let russian = PdnGame::parse("1. c3-d4 h6-g5 2. d4-e6 *").unwrap().or_variant(Variant::Russian);
+        assert_eq!(russian.to_game().err(), Some(PdnError::IllegalMove(3, "d4-e6".to_string())));
+        assert!(matches!(PdnGame::parse("1. c3-d4 {open"), Err(PdnError::Syntax(_))));
+        assert!(matches!(PdnGame::parse("1. c3-d4 (1. a3-b4"), Err(PdnError::Syntax(_))));
+        assert_eq!(PdnGame::parse("[GameType \"99\"]").unwrap().variant(),
+                   Err(PdnError::GameType("99".to_string())));
+    }
+}
@@ -0,0 +1,86 @@
+use std::mem::take;
+use crate::moves_list::MoveItem;
+use crate::position::Position;
+
+impl Position {
+    /// Number of move sequences of `depth` plies from the position, a check of the move generator.
+    /// Game end rules are not applied: a line ends only when the side to move has no moves.
+    pub fn perft(&mut self, depth: usize) -> u64 {
+        if depth == 0 { return 1; }
+        let list = self.get_move_list(false).list;
+        if depth == 1 { return list.len() as u64; }
+        list.iter().map(|mov| self.perft_after(mov, depth - 1)).sum()
+    }
+
+    /// Perft split by the first move, in the order of the move list.
+    pub fn divide(&mut self, depth: usize) -> Vec<(MoveItem, u64)> {
+        if depth == 0 { return vec![]; }
+        self.get_move_list(false).list.into_iter()
+            .map(|mov| {
+                let nodes = self.perft_after(&mov, depth - 1);
+                (mov, nodes)
+            })
+            .collect()
+    }
+
+    fn perft_after(&mut self, mov: &MoveItem, depth: usize) -> u64 {
+        self.make_move(mov);
+        // unmake_move needs the pieces this move took, the deeper moves overwrite them
+        let took_pieces = take(&mut self.took_pieces);
+        let nodes = self.perft(depth);
+        self.took_pieces = took_pieces;
+        self.unmake_move(mov);
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Game;
+    use crate::rules::Variant;
+
+    fn perft(variant: Variant, fen: Option<&str>, depth: usize) -> u64 {
+        let mut game = Game::new_standard(variant);
+        if let Some(fen) = fen { game.set_fen(fen).unwrap(); }
+        let start = game.current_position.clone();
+        let nodes = game.current_position.perft(depth);
+        assert!(game.current_position == start);
+        nodes
+    }
+
+    #[test]
+    fn start_positions() {
+        let cases: [(Variant, &[u64]); 3] = [
+            (Variant::Russian, &[1, 7, 49, 302, 1469, 7482, 37986]),
+            (Variant::English, &[1, 7, 49, 302, 1469, 7361, 36768]),
+            (Variant::International, &[1, 9, 81, 658, 4265, 27117]),
+        ];
+        for (variant, counts) in cases {
+            for (depth, count) in counts.iter().enumerate() {
+                assert_eq!(perft(variant, None, depth), *count, "{:?} depth {}", variant, depth);
+            }
+        }
+    }
+
+    #[test]
+    fn capture_positions() {
+        // the king on a1 from the readme
+        let readme = "W:WK29:B9,10,11,17,18,19,25,26,27";
+        assert_eq!(perft(Variant::Russian, Some(readme), 1), 42);
+        assert_eq!(perft(Variant::Pool, Some(readme), 1), 42);
+        // the man on b6 is crowned on d8 and takes e7 as a king, stopping on f6, g5 or h4
+        assert_eq!(perft(Variant::Russian, Some("W:W9:B6,7"), 1), 3);
+        assert_eq!(perft(Variant::Russian, Some("W:W9:B6,7"), 2), 0);
+        // English: the crowned man stops on d8
+        assert_eq!(perft(Variant::English, Some("W:W9:B6,7"), 1), 1);
+    }
+
+    #[test]
+    fn divide_sums_up() {
+        let mut pos = Game::new_standard(Variant::Russian).current_position;
+        let divide = pos.divide(4);
+        assert_eq!(divide.len(), 7);
+        assert_eq!(divide.iter().map(|x| x.1).sum::<u64>(), pos.perft(4));
+        assert!(pos.divide(0).is_empty());
+    }
+}
@@ -3,7 +3,9 @@ use std::hash::{Hash};
 use std::io;
 use std::io::Write;
 use std::mem::swap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+#[cfg(feature = "bench")]
+use std::sync::Mutex;
 use rand::{Rng};
 use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
@@ -16,6 +18,10 @@ use crate::piece::Piece;
 use ts_rs::*;
 use crate::mcts::VectorPosition;
 use crate::PositionHistory::PositionAndMove;
+use crate::rules::Promotion;
+use crate::zobrist::{black_to_move_key, king_moves_key, piece_key};
+use crate::bitboard;
+use crate::bitboard::BitBoard;
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[derive(TS)]
@@ -37,6 +43,12 @@ pub struct PosState {
     pub(crate) power_equal_start_at: Option<usize>,
     pub(crate) main_road_start_at: Option<usize>,
     pub(crate) repeats: u8,
+    // quiet moves made in a row by kings of each side
+    pub(crate) white_king_moves: u8,
+    pub(crate) black_king_moves: u8,
+    // evaluations are negated in antidraughts
+    #[serde(skip)]
+    pub(crate) losing: bool,
 }
 
 impl PartialEq for PosState {
@@ -63,6 +75,16 @@ impl PosState {
     pub fn get_count(&mut self, color: Color) -> &mut PieceCount {
         if color == Color::Black { &mut self.black } else { &mut self.white }
     }
+    pub fn get_king_moves(&mut self, color: Color) -> &mut u8 {
+        if color == Color::Black { &mut self.black_king_moves } else { &mut self.white_king_moves }
+    }
+    pub fn count(&self, color: Color) -> &PieceCount {
+        if color == Color::Black { &self.black } else { &self.white }
+    }
+    /// Quiet moves made in a row by the kings of `color`.
+    pub fn king_moves(&self, color: Color) -> u8 {
+        if color == Color::Black { self.black_king_moves } else { self.white_king_moves }
+    }
     pub fn get_total(&self) -> i32 {
         (self.black.king + self.black.simple + self.white.king + self.white.simple) as i32
     }
@@ -72,8 +94,9 @@ impl PosState {
     }
 
     pub fn evaluate(&self) -> i32 {
-        self.white.simple as i32 * 10000 + self.white.king as i32 * 30000
-            - self.black.simple as i32 * 10000 - self.black.king as i32 * 30000
+        let eval = self.white.simple as i32 * 10000 + self.white.king as i32 * 30000
+            - self.black.simple as i32 * 10000 - self.black.king as i32 * 30000;
+        if self.losing { -eval } else { eval }
     }
 }
 
@@ -92,7 +115,17 @@ pub struct Position {
     pub environment: Arc<PositionEnvironment>,
     #[serde(skip_serializing)]
     pub took_pieces: Vec<Option<Piece>>,
-    key: Option<VectorPosition>
+    // king moves counter of the side to move before each made move, for unmake_move
+    #[serde(skip)]
+    king_moves_undo: Vec<u8>,
+    key: Option<VectorPosition>,
+    // Zobrist hash of the pieces and the counted king moves, without the side to move
+    #[serde(skip)]
+    pieces_hash: u64,
+    // the pieces as sets of squares for the move generator
+    #[serde(skip)]
+    #[ts(skip)]
+    pub(crate) board: BitBoard<u128>,
 }
 
 impl Clone for Position {
@@ -105,7 +138,10 @@ impl Clone for Position {
             eval: self.eval.clone(),
             environment: self.environment.clone(),
             took_pieces: self.took_pieces.clone(),
+            king_moves_undo: self.king_moves_undo.clone(),
             key: None,
+            pieces_hash: self.pieces_hash,
+            board: self.board,
         }
     }
 }
@@ -117,7 +153,7 @@ pub struct TuplePositionKey(pub Arc<VectorPosition>, pub Arc<VectorPosition>);
 
 impl PartialEq for Position {
     fn eq(&self, other: &Self) -> bool {
-        self.next_move == other.next_move &&
+        self.hash() == other.hash() && self.next_move == other.next_move &&
             self.cells.iter().enumerate().all(|(i, x)| Some(&other.cells[i]) == Some(&x))
     }
 }
@@ -134,6 +170,9 @@ impl Position {
                 power_equal_start_at: None,
                 main_road_start_at: None,
                 repeats: 0,
+                white_king_moves: 0,
+                black_king_moves: 0,
+                losing: false,
             },
             cells: Vec::new(),
             environment,
@@ -141,11 +180,22 @@ impl Position {
             move_list: Arc::new(None),
             eval: None,
             took_pieces: vec![],
-            key: None
+            king_moves_undo: vec![],
+            key: None,
+            pieces_hash: 0,
+            board: BitBoard::default(),
         };
         pos.cells = Vec::new();
-        let size = pos.environment.size;
-        pos.cells.resize((size * size / 2) as usize, None);
+        pos.cells.resize(pos.environment.pack_to_board.len(), None);
+        pos.state.losing = pos.environment.rules.losing();
+        pos
+    }
+
+    /// Starting position of the environment's variant with its first player to move.
+    pub fn initial(environment: Arc<PositionEnvironment>) -> Position {
+        let mut pos = Position::new(environment.clone());
+        environment.initial_pieces().into_iter().for_each(|piece| pos.insert_piece(piece));
+        pos.next_move = Some(environment.rules.first_move());
         pos
     }
 
@@ -156,6 +206,28 @@ impl Position {
         self.key.as_mut().unwrap().clone()
     }
 
+    /// Zobrist hash of the pieces, the side to move and the king moves the rules count,
+    /// kept up to date by the moves.
+    pub fn hash(&self) -> u64 {
+        if self.next_move == Some(Color::Black) { self.pieces_hash ^ black_to_move_key() } else { self.pieces_hash }
+    }
+
+    // adds the piece on the square to the hash and the bitboard or takes it out
+    fn toggle_piece(&mut self, pos: BoardPos) {
+        if let Some(piece) = &self.cells[pos] {
+            self.pieces_hash ^= piece_key(piece, pos);
+            self.board.toggle(pos, piece.color, piece.is_king);
+        }
+    }
+
+    // adds the king moves counter of the color to the hash or takes it out
+    fn toggle_king_moves_hash(&mut self, color: Color) {
+        let limit = self.environment.rules.king_moves_limit();
+        if limit == 0 { return; }
+        let count = self.state.king_moves(color);
+        self.pieces_hash ^= king_moves_key(color, count.min(limit));
+    }
+
     pub fn print_pos(&self) {
         let pieces: Vec<_> = self.cells.iter().filter(|x| x.is_some()).collect();
         let pieces: Vec<_> = pieces.iter().map(|x| {
@@ -195,7 +267,9 @@ impl Position {
     pub fn insert_piece(&mut self, piece: Piece) {
         let pos = piece.pos as usize;
         self.state_change(&piece, 1);
+        self.toggle_piece(pos);
         self.cells[pos] = Some(piece);
+        self.toggle_piece(pos);
         self.move_list = Arc::new(None);
         self.eval = None;
     }
@@ -203,6 +277,7 @@ impl Position {
     pub fn remove_piece(&mut self, pos: BoardPos) -> bool {
         if let Some(piece) = self.cells[pos].clone() {
             self.state_change(&piece, -1);
+            self.toggle_piece(pos);
             self.cells[pos] = None;
             self.move_list = Arc::new(None);
             self.eval = None;
@@ -220,11 +295,13 @@ impl Position {
             }
         }
         if mov.is_king() {
+            self.toggle_piece(mov.to());
             let color = {
                 let piece = self.cells[mov.to()].as_mut().unwrap();
                 piece.is_king = true;
                 piece.color
             };
+            self.toggle_piece(mov.to());
             self.state_change_by_king_color(color, 1);
         }
     }
@@ -237,11 +314,13 @@ impl Position {
             }
         }
         if mov.is_king() {
+            self.toggle_piece(mov.from());
             let color = {
                 let piece = self.cells[mov.from()].as_mut().unwrap();
                 piece.is_king = false;
                 piece.color
             };
+            self.toggle_piece(mov.from());
             self.state_change_by_king_color(color, -1);
         }
     }
@@ -250,6 +329,8 @@ impl Position {
         &self.cells[v[i]]
     }
     pub fn swap(&mut self, i: BoardPos, j: BoardPos) {
+        self.toggle_piece(i);
+        self.toggle_piece(j);
         self.cells.swap(i as usize, j as usize);
         let set_pos = |cell: &mut Option<Piece>, pos: BoardPos| {
             if let Some(ref mut piece) = cell {
@@ -258,6 +339,8 @@ impl Position {
         };
         set_pos(&mut self.cells[i], i);
         set_pos(&mut self.cells[j], j);
+        self.toggle_piece(i);
+        self.toggle_piece(j);
     }
 
     fn straight_strike(&mut self, v: &Arc<Vec<BoardPos>>) -> Option<StraightStrike> {
@@ -265,12 +348,13 @@ impl Position {
             return None;
         }
         if let Some(piece) = self.get_piece_by_v(v, 0) {
-            let search_steps_top = if piece.is_king { v.len() } else { 3 };
+            let search_steps_top =
+                if piece.is_king && self.environment.rules.flying_kings() { v.len() } else { 3 };
             let mut i: usize = 2;
             while i < search_steps_top {
                 if let Some(candidate) = self.get_piece_by_v(v, i - 1) {
                     if self.get_piece_by_v(v, i).is_none() && candidate.color != piece.color
-                        && !candidate.stricken {
+                        && !candidate.stricken && self.environment.rules.can_take(piece, candidate) {
                         let strike = StraightStrike {
                             v: {
                                 let mut i_next = i;
@@ -296,13 +380,7 @@ impl Position {
     }
 
     fn get_vectors(&self, piece: &Piece, ban_directions: &Vec<i8>, for_strike: bool) -> Vec<Arc<Vector<BoardPos>>> {
-        let d2_4 = {
-            if piece.is_king || for_strike { vec![0, 1, 2, 3] } else if piece.color == Color::White {
-                vec![0, 1]
-            } else {
-                vec![2, 3]
-            }
-        };
+        let d2_4 = self.environment.rules.directions(piece, for_strike);
         let vectors = self.environment.get_vectors(piece.pos);
         let mut res = Vec::new();
         for v in vectors {
@@ -322,16 +400,17 @@ impl Position {
     pub fn get_quiet_move_list(
         &mut self,
         pos: BoardPos,
-        move_list:  &Arc<Mutex<MoveList>>,
+        move_list: &mut Vec<MoveItem>,
     ) -> bool {
         if let Some(piece) = &self.cells[pos] {
             let vectors: Vec<_> = self.get_vectors(piece, &vec![], false);
+            let flying = piece.is_king && self.environment.rules.flying_kings();
             for vector in vectors {
                 for point in {
-                    if piece.is_king { &(vector.points)[1..] } else { &(vector.points)[1..2] }
+                    if flying { &(vector.points)[1..] } else { &(vector.points)[1..2] }
                 } {
                     if self.cells[*point].is_some() { break; }
-                    move_list.lock().unwrap().list.push(
+                    move_list.push(
                         MoveItem {
                             mov: Some(QuietMove {
                                 from: pos,
@@ -342,7 +421,7 @@ impl Position {
                         })
                 }
             }
-            return move_list.lock().unwrap().list.len() > 0;
+            return !move_list.is_empty();
         }
         false
     }
@@ -387,6 +466,7 @@ impl Position {
                 }
             }
         }
+        if self.state.losing { eval = -eval; }
         eval += self.state.evaluate();
         self.eval = Some(eval);
         eval
@@ -395,13 +475,15 @@ impl Position {
     pub fn get_strike_list(
         &mut self,
         pos: BoardPos,
-        move_list: &Arc<Mutex<MoveList>>,
+        move_list: &mut Vec<MoveItem>,
         ban_directions: &Vec<i8>,
         for_front: bool,
         current_chain: &mut Strike
     ) -> bool {
         let mut success_call = false;
         if let Some(piece) = &self.cells[pos] {
+            let promotion = self.environment.rules.promotion();
+            let removes_at_once = self.environment.rules.removes_at_once();
             let vectors: Vec<_> = self.get_vectors(piece, ban_directions, true);
             for v in vectors {
                 let points = &v.points;
@@ -411,29 +493,46 @@ impl Position {
                     let mut ban_directions = vec![v.get_ban_direction()];
                     let mut recurrent_chain = false;
                     let mut strike_move = straight_strike.clone();
+                    // the man is crowned only where the whole move ends
+                    if promotion == Promotion::EndOfMove { strike_move.king_move = false; }
+                    // the taken piece is lifted while the capture goes on, its square becomes free
+                    if removes_at_once { self.toggle_piece(straight_strike.take); }
+                    let lifted = if removes_at_once { self.cells[straight_strike.take].take() } else { None };
                     for pos in &straight_strike {
                         strike_move.to = pos;
                         self.make_strike_or_move(&mut strike_move);
-                        current_chain.vec.push(strike_move.clone());
-                        if strike_move.king_move { current_chain.king_move = true; }
-                        if self.get_strike_list(pos, move_list, &ban_directions, for_front, current_chain) {
+                        let king_move = strike_move.king_move;
+                        if king_move { current_chain.king_move = true; }
+                        let crowned_stop = king_move && promotion == Promotion::StopsStrike;
+                        // the strike goes into the chain and back, it is not copied for every branch
+                        current_chain.vec.push(strike_move);
+                        if !crowned_stop &&
+                            self.get_strike_list(pos, move_list, &ban_directions, for_front, current_chain) {
                             recurrent_chain = true;
                         }
-                        current_chain.vec.pop();
-                        if strike_move.king_move { current_chain.king_move = false; }
+                        strike_move = current_chain.vec.pop().unwrap();
+                        if king_move { current_chain.king_move = false; }
                         self.unmake_strike_or_move(&strike_move);
                         if !for_front && ban_directions.len() < 2 {
                             ban_directions.push(v.direction);
                         }
                     }
+                    if removes_at_once {
+                        self.cells[straight_strike.take] = lifted;
+                        self.toggle_piece(straight_strike.take);
+                    }
                     if !recurrent_chain {
                         for pos in &straight_strike {
                             let mut strike_move = straight_strike.clone();
                             strike_move.to = pos;
+                            if promotion == Promotion::EndOfMove {
+                                strike_move.king_move = self.environment
+                                    .is_king_move_for(self.get_piece_in_pos(straight_strike.from), pos);
+                            }
                             let mut chain = current_chain.clone();
                             if strike_move.king_move { chain.king_move = true; }
                             chain.vec.push(strike_move);
-                            move_list.lock().unwrap().list.push(MoveItem { strike: Some(chain), mov: None });
+                            move_list.push(MoveItem { strike: Some(chain), mov: None });
                         }
                     }
                 }
@@ -443,11 +542,21 @@ impl Position {
     }
 
     pub fn make_move(&mut self, move_item: &MoveItem) {
+        if let Some(color) = self.next_move {
+            let king_quiet = move_item.mov.as_ref()
+                .is_some_and(|mov| self.cells[mov.from].as_ref().is_some_and(|piece| piece.is_king));
+            self.toggle_king_moves_hash(color);
+            let king_moves = self.state.get_king_moves(color);
+            self.king_moves_undo.push(*king_moves);
+            *king_moves = if king_quiet { king_moves.saturating_add(1) } else { 0 };
+            self.toggle_king_moves_hash(color);
+        }
         if let Some(ref mov) = move_item.mov {
             self.make_strike_or_move(mov);
         } else if let Some(ref strike) = move_item.strike {
             self.took_pieces = vec![None; strike.vec.len()];
             for (i, straight_strike) in strike.vec.iter().enumerate() {
+                self.toggle_piece(straight_strike.take);
                 swap(&mut self.took_pieces[i], &mut self.cells[straight_strike.take]);
                 self.state_change(self.took_pieces[i].clone().as_ref().unwrap(), -1);
             };
@@ -472,6 +581,7 @@ impl Position {
             for (i, straight_strike) in strike.vec.iter().enumerate() {
                 self.state_change(self.took_pieces[i].clone().as_ref().unwrap(), 1);
                 swap(&mut self.took_pieces[i], &mut self.cells[straight_strike.take]);
+                self.toggle_piece(straight_strike.take);
             };
             let ref mut mov = QuietMove {
                 from: strike.vec[0].from,
@@ -481,6 +591,14 @@ impl Position {
             self.unmake_strike_or_move(mov);
         }
         if self.next_move.is_some() { self.next_move = Some(!self.next_move.unwrap()) }
+        if let (Some(color), Some(king_moves)) = (self.next_move, self.king_moves_undo.pop()) {
+            self.toggle_king_moves_hash(color);
+            *self.state.get_king_moves(color) = king_moves;
+            self.toggle_king_moves_hash(color);
+        }
+        // the caches may hold the list and the evaluation of a position after the move
+        self.move_list = Arc::new(None);
+        self.eval = None;
     }
 
     pub fn make_move_and_get_position(&mut self, move_item: &MoveItem) -> PositionAndMove {
@@ -488,7 +606,49 @@ impl Position {
         PositionAndMove::from(self.clone(), move_item.clone())
     }
 
+    /// Moves of the side to move: all captures allowed by the rules if there are any,
+    /// quiet moves otherwise. `for_front` keeps the capture paths through every landing square.
     pub fn get_move_list(&mut self, for_front: bool) -> MoveList {
+        let mut list = Vec::new();
+        self.get_moves_into(for_front, &mut list);
+        MoveList { list }
+    }
+
+    /// `get_move_list` into the caller's buffer, which is cleared first and keeps its capacity.
+    pub fn get_moves_into(&mut self, for_front: bool, list: &mut Vec<MoveItem>) {
+        bitboard::generate(self, for_front, list);
+    }
+
+    /// The same list generated on the cells, the reference for `bitboard::generate`.
+    pub fn get_move_list_by_cells(&mut self, for_front: bool) -> MoveList {
+        let color = self.next_move.unwrap_or_else(|| panic!("Color of next move undefined!"));
+        let pieces_pos: Vec<_> = self.cells.iter()
+            .filter(|piece| if let Some(piece) = piece { piece.color == color } else { false })
+            .map(|piece| if let Some(piece) =
+                piece { piece.pos } else { panic!("Position problem in get_move_list"); })
+            .collect();
+        let mut list = Vec::new();
+        for pos in &pieces_pos {
+            self.get_strike_list(*pos, &mut list, &vec![], for_front, &mut Strike::new());
+        }
+        let rules = self.environment.rules.clone();
+        if !list.is_empty() {
+            rules.filter_strikes(self, &mut list);
+        } else {
+            for pos in pieces_pos {
+                self.get_quiet_move_list(pos, &mut list);
+            }
+            rules.filter_moves(self, &mut list);
+        }
+        MoveList { list }
+    }
+}
+
+/// The cells generator as it was before `get_moves_into`: every move is pushed under the lock
+/// of a shared list, which is cloned out at the end. Only the move generation bench uses it.
+#[cfg(feature = "bench")]
+impl Position {
+    pub fn get_move_list_locked(&mut self, for_front: bool) -> MoveList {
         let color = self.next_move.unwrap_or_else(|| panic!("Color of next move undefined!"));
         let pieces_pos: Vec<_> = self.cells.iter()
             .filter(|piece| if let Some(piece) = piece { piece.color == color } else { false })
@@ -496,26 +656,112 @@ impl Position {
                 piece { piece.pos } else { panic!("Position problem in get_move_list"); })
             .collect();
         let move_list = Arc::new(Mutex::new(MoveList::new()));
-        // let posit_list = Arc::new(Mutex::new(
-        //     pieces_pos.iter().map(|_|self.clone()).collect::<Vec<_>>()));
-        // pieces_pos.par_iter().enumerate().for_each(|(i,x)|{
-        //     posit_list.lock().unwrap()[i].get_strike_list(*x, &move_list, &vec![], for_front,
-        //                                  &mut Strike::new());
-        // });
         for pos in &pieces_pos {
-            self.get_strike_list(*pos, &move_list, &vec![], for_front, &mut Strike::new());
+            self.get_strike_list_locked(*pos, &move_list, &vec![], for_front, &mut Strike::new());
         }
-        if move_list.lock().unwrap().list.is_empty() {
-            // pieces_pos.par_iter().enumerate().for_each(|(i,x)|{
-            //     posit_list.lock().unwrap()[i].get_quiet_move_list(*x, &move_list);
-            // });
+        if !move_list.lock().unwrap().list.is_empty() {
+            let rules = self.environment.rules.clone();
+            rules.filter_strikes(self, &mut move_list.lock().unwrap().list);
+        } else {
             for pos in pieces_pos {
-                self.get_quiet_move_list(pos, &move_list);
+                self.get_quiet_move_list_locked(pos, &move_list);
             }
+            let rules = self.environment.rules.clone();
+            rules.filter_moves(self, &mut move_list.lock().unwrap().list);
         }
         let ret = move_list.lock().unwrap();
         ret.clone()
     }
+
+    fn get_quiet_move_list_locked(&mut self, pos: BoardPos, move_list: &Arc<Mutex<MoveList>>) -> bool {
+        if let Some(piece) = &self.cells[pos] {
+            let vectors: Vec<_> = self.get_vectors(piece, &vec![], false);
+            let flying = piece.is_king && self.environment.rules.flying_kings();
+            for vector in vectors {
+                for point in {
+                    if flying { &(vector.points)[1..] } else { &(vector.points)[1..2] }
+                } {
+                    if self.cells[*point].is_some() { break; }
+                    move_list.lock().unwrap().list.push(
+                        MoveItem {
+                            mov: Some(QuietMove {
+                                from: pos,
+                                to: *point,
+                                king_move: self.environment.is_king_move_for(piece, *point),
+                            }),
+                            strike: None,
+                        })
+                }
+            }
+            return !move_list.lock().unwrap().list.is_empty();
+        }
+        false
+    }
+
+    fn get_strike_list_locked(
+        &mut self,
+        pos: BoardPos,
+        move_list: &Arc<Mutex<MoveList>>,
+        ban_directions: &Vec<i8>,
+        for_front: bool,
+        current_chain: &mut Strike
+    ) -> bool {
+        let mut success_call = false;
+        if let Some(piece) = &self.cells[pos] {
+            let promotion = self.environment.rules.promotion();
+            let removes_at_once = self.environment.rules.removes_at_once();
+            let vectors: Vec<_> = self.get_vectors(piece, ban_directions, true);
+            for v in vectors {
+                let points = &v.points;
+                let strike = self.straight_strike(points);
+                if let Some(straight_strike) = strike {
+                    success_call = true;
+                    let mut ban_directions = vec![v.get_ban_direction()];
+                    let mut recurrent_chain = false;
+                    let mut strike_move = straight_strike.clone();
+                    if promotion == Promotion::EndOfMove { strike_move.king_move = false; }
+                    if removes_at_once { self.toggle_piece(straight_strike.take); }
+                    let lifted = if removes_at_once { self.cells[straight_strike.take].take() } else { None };
+                    for pos in &straight_strike {
+                        strike_move.to = pos;
+                        self.make_strike_or_move(&mut strike_move);
+                        current_chain.vec.push(strike_move.clone());
+                        if strike_move.king_move { current_chain.king_move = true; }
+                        let crowned_stop = strike_move.king_move && promotion == Promotion::StopsStrike;
+                        if !crowned_stop &&
+                            self.get_strike_list_locked(pos, move_list, &ban_directions, for_front, current_chain) {
+                            recurrent_chain = true;
+                        }
+                        current_chain.vec.pop();
+                        if strike_move.king_move { current_chain.king_move = false; }
+                        self.unmake_strike_or_move(&strike_move);
+                        if !for_front && ban_directions.len() < 2 {
+                            ban_directions.push(v.direction);
+                        }
+                    }
+                    if removes_at_once {
+                        self.cells[straight_strike.take] = lifted;
+                        self.toggle_piece(straight_strike.take);
+                    }
+                    if !recurrent_chain {
+                        for pos in &straight_strike {
+                            let mut strike_move = straight_strike.clone();
+                            strike_move.to = pos;
+                            if promotion == Promotion::EndOfMove {
+                                strike_move.king_move = self.environment
+                                    .is_king_move_for(self.get_piece_in_pos(straight_strike.from), pos);
+                            }
+                            let mut chain = current_chain.clone();
+                            if strike_move.king_move { chain.king_move = true; }
+                            chain.vec.push(strike_move);
+                            move_list.lock().unwrap().list.push(MoveItem { strike: Some(chain), mov: None });
+                        }
+                    }
+                }
+            }
+        }
+        success_call
+    }
 }
 
 #[cfg(test)]
@@ -2,10 +2,10 @@ use std::borrow::BorrowMut;
 use crate::position::Position;
 use crate::moves::BoardPos;
 use crate::color::Color;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::fmt::Debug;
 use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::JsValue;
 use crate::moves_list::{MoveList, Strike};
@@ -13,6 +13,8 @@ use crate::piece::Piece;
 use ts_rs::TS;
 use crate::game::Game;
 use crate::vector::Vector;
+use crate::rules::{default_rules, RuleSet, Variant};
+use crate::bitboard::Shifts;
 
 #[derive(Clone, Deserialize, Serialize, Debug, TS)]
 #[ts(export)]
@@ -33,7 +35,7 @@ impl Grade {
 }
 
 #[wasm_bindgen]
-#[derive(Clone, Deserialize, Serialize, Debug, TS)]
+#[derive(Clone, Serialize, Debug, TS)]
 #[ts(export)]
 pub struct PositionEnvironment {
     pub size: i8,
@@ -43,80 +45,38 @@ pub struct PositionEnvironment {
     pub(crate) board_to_pack: Vec<BoardPos>,
     pub(crate) pack_to_board: Vec<BoardPos>,
     pub(crate) cell_grade: Vec<Grade>,
+    #[serde(skip)]
+    #[ts(skip)]
+    pub(crate) rules: Arc<dyn RuleSet>,
+    // the variant the rules came from, if any
+    pub(crate) variant: Option<Variant>,
+    #[serde(skip)]
+    #[ts(skip)]
+    pub(crate) shifts: Shifts,
+}
+
+// the rules are not stored, they are built again from the variant
+#[derive(Deserialize)]
+struct StoredEnvironment {
+    size: i8,
+    variant: Option<Variant>,
+}
+
+impl<'de> Deserialize<'de> for PositionEnvironment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored = StoredEnvironment::deserialize(deserializer)?;
+        Ok(match stored.variant {
+            Some(variant) => PositionEnvironment::for_variant(variant),
+            None => PositionEnvironment::new(stored.size),
+        })
+    }
 }
 
 #[wasm_bindgen]
 impl PositionEnvironment {
     #[wasm_bindgen(constructor)]
     pub fn new(size: i8) -> Self {
-        if size % 2 != 0 {
-            panic!("Size must be even")
-        }
-        let size2 = (size * size) as BoardPos;
-        let is_black_cell = |i: BoardPos| -> bool { (i / size as BoardPos + i % 2) % 2 == 0 };
-        let is_on_board = |i: BoardPos| -> bool { i < size2 && is_black_cell(i) };
-        let d4 = vec![size + 1, size - 1, -(size + 1), -(size - 1)];
-        let mut vectors_map = Vec::new();
-        let mut board_to_pack: Vec<BoardPos> = Vec::new();
-        board_to_pack.resize(size2 as usize, 0);
-        let mut pack_to_board: Vec<BoardPos> = Vec::with_capacity((size2 / 2) as usize);
-        pack_to_board.resize((size2 / 2) as usize, 0);
-        // packing board is array with only black cells
-        let mut j: BoardPos = 0;
-        for i in 0..size2 as BoardPos {
-            if is_black_cell(i) {
-                board_to_pack[i] = j;
-                pack_to_board[j] = i;
-                j += 1;
-            }
-        }
-        // vectors_map for packing board
-        for i in 0..size2 {
-            if is_black_cell(i) {
-                let mut direction_index: i8 = 0;
-                let mut d4_v_list = Vec::new();
-                for d in d4.iter() {
-                    let mut p = i;
-
-                    let mut points = vec![board_to_pack[p]];
-                    loop {
-                        p = ((p as i64) + (*d as i64)) as BoardPos;
-                        if !is_on_board(p) {
-                            break;
-                        }
-                        points.push(board_to_pack[p as usize]);
-                    }
-                    let v: Vector<BoardPos> =
-                        Vector::new(direction_index, points);
-
-                    if v.points.len() > 1 {
-                        d4_v_list.push(Arc::new(v));
-                    }
-                    direction_index += 1;
-                }
-                vectors_map.push(d4_v_list);
-            }
-        }
-        let mut cell_grade: Vec<Grade> = Vec::new();
-        for v in &vectors_map {
-            let mut b = 0;
-            let mut k: i16 = 0;
-            let mut w = 0;
-            for v_d in v {
-                if v_d.direction < 2 { w += 1; } else { b += 1; }
-                k += v_d.points.len() as i16;
-            }
-            cell_grade.push(Grade { white: w, is_king: k, black: b })
-        }
-        PositionEnvironment {
-            pack_to_board,
-            board_to_pack,
-            cell_grade,
-            vectors_map,
-            size,
-            king_row_black: size as usize / 2,
-            king_row_white: (size2 - size as usize) / 2 - 1,
-        }
+        PositionEnvironment::with_rules(size, default_rules())
     }
 
 
@@ -208,17 +168,17 @@ impl PositionEnvironment {
         //
 
         for _i in 0..100000 {
-            let mut list = Arc::new(Mutex::new(MoveList::new()));
-            pos.get_strike_list(22, &list, &vec![], false, &mut Strike::new());
-            let p0 = pos.make_move_and_get_position(&mut list.lock().unwrap().clone().list[0]);
+            let mut list = MoveList::new();
+            pos.get_strike_list(22, &mut list.list, &vec![], false, &mut Strike::new());
+            let p0 = pos.make_move_and_get_position(&list.list[0]);
             pos.unmake_move(&p0.mov.unwrap());
             let p1 = p0.pos.clone();
             if p0.pos != p1 { break; }
         };
 
 
-        let list = Arc::new(Mutex::new(MoveList::new()));
-        pos.get_strike_list(22, &list, &vec![], false, &mut Strike::new());
+        let mut list = MoveList::new();
+        pos.get_strike_list(22, &mut list.list, &vec![], false, &mut Strike::new());
         match serde_wasm_bindgen::to_value(&list) {
             Ok(js) => js,
             Err(_err) => JsValue::UNDEFINED,
@@ -233,7 +193,187 @@ impl PositionEnvironment {
 }
 
 impl PositionEnvironment {
+    pub fn with_rules(size: i8, rules: Arc<dyn RuleSet>) -> Self {
+        if size % 2 != 0 {
+            panic!("Size must be even")
+        }
+        let size2 = size as BoardPos * size as BoardPos;
+        // mirrored boards have a light square in the lower left corner
+        let shift = rules.mirrored_board() as BoardPos;
+        let full_board = rules.full_board();
+        let is_black_cell = |i: BoardPos| -> bool {
+            full_board || (i / size as BoardPos + i % 2 + shift) % 2 == 0
+        };
+        // row and column steps of directions 0..7: UR, UL, DL, DR, U, L, D, R;
+        // orthogonal steps jump over the light square when only dark squares are used
+        let o: i64 = if full_board { 1 } else { 2 };
+        let steps: [(i64, i64); 8] = [(1, 1), (1, -1), (-1, -1), (-1, 1), (o, 0), (0, -o), (-o, 0), (0, o)];
+        let mut vectors_map = Vec::new();
+        let mut board_to_pack: Vec<BoardPos> = Vec::new();
+        board_to_pack.resize(size2, 0);
+        let mut pack_to_board: Vec<BoardPos> = Vec::new();
+        // packing board is array with only black cells
+        for i in 0..size2 as BoardPos {
+            if is_black_cell(i) {
+                board_to_pack[i] = pack_to_board.len();
+                pack_to_board.push(i);
+            }
+        }
+        // vectors_map for packing board
+        for i in 0..size2 {
+            if is_black_cell(i) {
+                let mut d_v_list = Vec::new();
+                for direction in rules.board_directions() {
+                    let (dr, dc) = steps[*direction as usize];
+                    let (mut r, mut c) = ((i / size as BoardPos) as i64, (i % size as BoardPos) as i64);
+                    let mut points = vec![board_to_pack[i]];
+                    loop {
+                        r += dr;
+                        c += dc;
+                        if r < 0 || c < 0 || r >= size as i64 || c >= size as i64 {
+                            break;
+                        }
+                        points.push(board_to_pack[(r * size as i64 + c) as BoardPos]);
+                    }
+                    let v: Vector<BoardPos> =
+                        Vector::new(*direction, points);
+
+                    if v.points.len() > 1 {
+                        d_v_list.push(Arc::new(v));
+                    }
+                }
+                vectors_map.push(d_v_list);
+            }
+        }
+        let mut cell_grade: Vec<Grade> = Vec::new();
+        for v in &vectors_map {
+            let mut b = 0;
+            let mut k: i16 = 0;
+            let mut w = 0;
+            for v_d in v {
+                match v_d.direction {
+                    0 | 1 | 4 => w += 1,
+                    2 | 3 | 6 => b += 1,
+                    _ => {}
+                }
+                k += v_d.points.len() as i16;
+            }
+            cell_grade.push(Grade { white: w, is_king: k, black: b })
+        }
+        // playable squares in the first and the last row
+        let row_len = pack_to_board.iter().take_while(|x| **x < size as BoardPos).count();
+        let cells = pack_to_board.len();
+        PositionEnvironment {
+            shifts: Shifts::from_vectors(&vectors_map),
+            pack_to_board,
+            board_to_pack,
+            cell_grade,
+            vectors_map,
+            size,
+            king_row_black: row_len,
+            king_row_white: cells - row_len - 1,
+            rules,
+            variant: None,
+        }
+    }
+
+    pub fn for_variant(variant: Variant) -> Self {
+        let mut environment = PositionEnvironment::with_rules(variant.size(), variant.rules());
+        environment.variant = Some(variant);
+        environment
+    }
+
+    /// Men of both sides on their starting squares: the rules' start rows for White,
+    /// the mirrored rows for Black.
+    pub fn initial_pieces(&self) -> Vec<Piece> {
+        let size = self.size as BoardPos;
+        let rows = self.rules.start_rows(size);
+        (0..self.pack_to_board.len()).filter_map(|pos| {
+            let row = self.pack_to_board[pos] / size;
+            if rows.contains(&row) {
+                Some(Piece::new(pos, Color::White, false))
+            } else if rows.contains(&(size - 1 - row)) {
+                Some(Piece::new(pos, Color::Black, false))
+            } else { None }
+        }).collect()
+    }
+
+    /// Number of the playable squares, the largest square number of the notation.
+    pub fn squares(&self) -> usize {
+        self.pack_to_board.len()
+    }
+
+    /// Packed index of a notation square: squares are numbered from 1 row by row, starting
+    /// with the top left playable square as White sees the board.
+    pub fn square_to_pack(&self, square: usize) -> Option<BoardPos> {
+        if square == 0 || square > self.squares() { return None; }
+        let in_row = self.squares() / self.size as usize;
+        let row = self.size as usize - 1 - (square - 1) / in_row;
+        Some(row * in_row + (square - 1) % in_row)
+    }
+
+    pub fn pack_to_square(&self, pos: BoardPos) -> usize {
+        let in_row = self.squares() / self.size as usize;
+        (self.size as usize - 1 - pos / in_row) * in_row + pos % in_row + 1
+    }
+
     pub fn get_vectors(&self, pos: usize) -> &Vec<Arc<Vector<BoardPos>>> {
         &self.vectors_map[pos]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+    use crate::moves::BoardPos;
+    use crate::piece::Piece;
+    use crate::position_environment::PositionEnvironment;
+    use crate::rules::Variant;
+
+    #[test]
+    fn serde_keeps_rules() {
+        for variant in [Variant::Russian, Variant::International, Variant::Antidraughts, Variant::Italian] {
+            let environment = PositionEnvironment::for_variant(variant);
+            let json = serde_json::to_string(&environment).unwrap();
+            let read: PositionEnvironment = serde_json::from_str(&json).unwrap();
+            assert_eq!(read.variant, Some(variant));
+            assert_eq!(read.rules.losing(), variant == Variant::Antidraughts);
+            assert_eq!(read.pack_to_board, environment.pack_to_board);
+        }
+        let read: PositionEnvironment = serde_json::from_str(&serde_json::to_string(&PositionEnvironment::new(10)).unwrap()).unwrap();
+        assert_eq!((read.size, read.variant), (10, None));
+    }
+
+    #[test]
+    fn board_12x12() {
+        let environment = PositionEnvironment::for_variant(Variant::Canadian);
+        assert_eq!(environment.pack_to_board.len(), 72);
+        assert_eq!(environment.vectors_map.len(), 72);
+        let pack = |board: BoardPos| environment.board_to_pack[board];
+        // a1 has only the main road, 12 squares up to l12
+        let v = &environment.vectors_map[pack(0)];
+        assert_eq!(v.len(), 1);
+        assert_eq!(*v[0].points, (0..12).map(|i| pack(i * 13)).collect::<Vec<_>>());
+        // f6 has all four directions, down left to a1 and up right to l12
+        let v = &environment.vectors_map[pack(65)];
+        assert_eq!(v.iter().map(|x| x.direction).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(v.iter().map(|x| x.points.len()).collect::<Vec<_>>(), vec![7, 6, 6, 6]);
+        // the grade counts directions up for white men, down for black men and squares for kings
+        let grade = &environment.cell_grade[pack(0)];
+        assert_eq!((grade.white, grade.black, grade.is_king), (1, 0, 12));
+        let grade = &environment.cell_grade[pack(65)];
+        assert_eq!((grade.white, grade.black, grade.is_king), (2, 2, 25));
+    }
+
+    #[test]
+    fn king_rows_12x12() {
+        let environment = PositionEnvironment::for_variant(Variant::Canadian);
+        let white = Piece::new(0, Color::White, false);
+        let black = Piece::new(0, Color::Black, false);
+        for pos in 0..environment.pack_to_board.len() {
+            let row = environment.pack_to_board[pos] / 12;
+            assert_eq!(environment.is_king_move_for(&white, pos), row == 11);
+            assert_eq!(environment.is_king_move_for(&black, pos), row == 0);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::mem::take;
+use crate::color::Color;
+use crate::position::Position;
+
+impl Position {
+    /// Value of the position once the captures are over: the side to move keeps capturing
+    /// while it has to, and only a quiet position gets the static `evaluate`.
+    /// White's advantage if positive, searched in White's window from `alpha` to `beta`.
+    pub fn quiescence(&mut self, mut alpha: i32, mut beta: i32, state_only: bool) -> i32 {
+        let move_list = self.get_move_list_cached();
+        let list = &move_list.as_ref().as_ref().unwrap().list;
+        if list.first().is_none_or(|mov| mov.strike.is_none()) {
+            return self.evaluate(state_only);
+        }
+        let white = self.next_move == Some(Color::White);
+        let mut best = if white { i32::MIN / 2 } else { i32::MAX / 2 };
+        for mov in list {
+            self.make_move(mov);
+            // unmake_move needs the pieces this capture took, the deeper ones overwrite them
+            let took_pieces = take(&mut self.took_pieces);
+            let eval = self.quiescence(alpha, beta, state_only);
+            self.took_pieces = took_pieces;
+            self.unmake_move(mov);
+            if white {
+                best = best.max(eval);
+                alpha = alpha.max(eval);
+            } else {
+                best = best.min(eval);
+                beta = beta.min(eval);
+            }
+            if alpha >= beta { break; }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::take;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use crate::color::Color;
+    use crate::game::Game;
+    use crate::position::Position;
+    use crate::rules::Variant;
+
+    // every capture line to the end, without cutoffs
+    fn captures_minimax(pos: &mut Position) -> i32 {
+        let list = pos.get_move_list(false).list;
+        if list.first().is_none_or(|mov| mov.strike.is_none()) {
+            return pos.clone().evaluate(true);
+        }
+        let white = pos.next_move == Some(Color::White);
+        let evals = list.iter().map(|mov| {
+            pos.make_move(mov);
+            let took_pieces = take(&mut pos.took_pieces);
+            let eval = captures_minimax(pos);
+            pos.took_pieces = took_pieces;
+            pos.unmake_move(mov);
+            eval
+        });
+        if white { evals.max().unwrap() } else { evals.min().unwrap() }
+    }
+
+    #[test]
+    fn same_as_minimax() {
+        let mut rng = StdRng::seed_from_u64(23);
+        let mut captures = 0;
+        for variant in [Variant::Russian, Variant::International, Variant::English, Variant::Frisian] {
+            for _ in 0..10 {
+                let mut pos = Game::new_standard(variant).current_position;
+                for _ in 0..80 {
+                    let list = pos.get_move_list(false).list;
+                    if list.is_empty() { break; }
+                    if list[0].strike.is_some() { captures += 1; }
+                    let start = pos.clone();
+                    let eval = pos.quiescence(i32::MIN / 2, i32::MAX / 2, true);
+                    assert!(pos == start, "{:?}", variant);
+                    assert_eq!(eval, captures_minimax(&mut pos), "{:?}", variant);
+                    pos.make_move(&list[rng.gen_range(0..list.len())]);
+                }
+            }
+        }
+        assert!(captures > 100, "{}", captures);
+    }
+
+    #[test]
+    fn the_king_takes_all() {
+        let mut game = Game::new_variant(Variant::Russian);
+        game.set_fen("W:WK29:B9,10,11,17,18,19,25,26,27").unwrap();
+        let pos = &mut game.current_position;
+        assert!(pos.clone().evaluate(true) < 0);
+        // Black is left without pieces
+        assert!(pos.quiescence(i32::MIN / 2, i32::MAX / 2, true) >= i32::MAX / 4);
+    }
+}
@@ -0,0 +1,830 @@
+use std::cmp::Reverse;
+use std::fmt::Debug;
+use std::ops::Range;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use wasm_bindgen::prelude::wasm_bindgen;
+use crate::color::Color;
+use crate::moves::BoardPos;
+use crate::moves_list::MoveItem;
+use crate::piece::Piece;
+use crate::position::Position;
+use crate::PositionHistory::{FinishType, PositionHistory};
+
+// directions of Vector: 0 - UR, 1 - UL, 2 - DL, 3 - DR, 4 - U, 5 - L, 6 - D, 7 - R
+const ALL_DIRECTIONS: [i8; 4] = [0, 1, 2, 3];
+const WHITE_FORWARD: [i8; 2] = [0, 1];
+const BLACK_FORWARD: [i8; 2] = [2, 3];
+const ORTHOGONAL: [i8; 4] = [4, 5, 6, 7];
+const WHITE_ORTHOGONAL: [i8; 3] = [4, 5, 7];
+const BLACK_ORTHOGONAL: [i8; 3] = [6, 5, 7];
+const DIAGONAL_AND_ORTHOGONAL: [i8; 8] = [0, 1, 2, 3, 4, 5, 6, 7];
+
+/// When a man that reaches the far row becomes a king.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Promotion {
+    /// at once, and the capture goes on with king moves
+    MidStrike,
+    /// only when the whole move ends on the far row
+    EndOfMove,
+    /// at once, and the capture ends there
+    StopsStrike,
+}
+
+/// Rules of a draughts variant. `PositionEnvironment` carries one and the move generator
+/// and `PositionHistory::finish_check` ask it instead of hard-coding Russian draughts.
+pub trait RuleSet: Debug + Send + Sync {
+    /// Directions the piece may use, for quiet moves or for captures.
+    fn directions(&self, piece: &Piece, for_strike: bool) -> &'static [i8] {
+        if piece.is_king || for_strike { &ALL_DIRECTIONS } else { forward(piece.color) }
+    }
+
+    /// Kings move and capture along the whole line if true, otherwise one square like a man.
+    fn flying_kings(&self) -> bool { true }
+
+    fn promotion(&self) -> Promotion { Promotion::MidStrike }
+
+    fn first_move(&self) -> Color { Color::White }
+
+    /// False if the piece is not allowed to capture the candidate at all.
+    fn can_take(&self, _piece: &Piece, _candidate: &Piece) -> bool { true }
+
+    /// The board lies with a light square in the lower left corner.
+    fn mirrored_board(&self) -> bool { false }
+
+    /// Pieces stand on all squares, not only on the dark ones.
+    fn full_board(&self) -> bool { false }
+
+    /// Directions `PositionEnvironment` builds vectors for.
+    fn board_directions(&self) -> &'static [i8] { &ALL_DIRECTIONS }
+
+    /// Rows White men start on, Black starts on the mirrored ones.
+    fn start_rows(&self, size: BoardPos) -> Range<BoardPos> { 0..size / 2 - 1 }
+
+    /// Taken pieces leave the board one by one during the capture, not all at its end,
+    /// so their squares may be crossed again.
+    fn removes_at_once(&self) -> bool { false }
+
+    /// Leaves only the captures the player may choose from. Default is a free choice.
+    fn filter_strikes(&self, _pos: &Position, _list: &mut Vec<MoveItem>) {}
+
+    /// The side left without pieces or moves wins, evaluations change their sign.
+    fn losing(&self) -> bool { false }
+
+    /// Leaves only the quiet moves the player may choose from, when there is no capture.
+    fn filter_moves(&self, _pos: &Position, _list: &mut Vec<MoveItem>) {}
+
+    /// Quiet king moves in a row after which `filter_moves` restricts the kings, 0 if it never does.
+    /// The position hash tells the counters apart up to it.
+    fn king_moves_limit(&self) -> u8 { 0 }
+
+    /// Draw rules, called by `finish_check` when the side to move still has moves.
+    fn draw_check(&self, history: &mut PositionHistory) -> Option<FinishType>;
+}
+
+fn forward(color: Color) -> &'static [i8] {
+    if color == Color::White { &WHITE_FORWARD } else { &BLACK_FORWARD }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Russian;
+
+impl RuleSet for Russian {
+    fn draw_check(&self, history: &mut PositionHistory) -> Option<FinishType> {
+        history.russian_draw_check()
+    }
+}
+
+/// International (FMJD) draughts: men promote only at the end of a move
+/// and the capture taking the most pieces is mandatory.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct International;
+
+impl RuleSet for International {
+    fn promotion(&self) -> Promotion { Promotion::EndOfMove }
+
+    fn filter_strikes(&self, _pos: &Position, list: &mut Vec<MoveItem>) {
+        majority_strikes(list);
+    }
+
+    fn draw_check(&self, history: &mut PositionHistory) -> Option<FinishType> {
+        history.fmjd_draw_check()
+    }
+}
+
+/// English draughts (American checkers): short kings, men capture only forward, crowning
+/// ends the capture, any capture may be chosen and Black moves first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct English;
+
+impl RuleSet for English {
+    fn directions(&self, piece: &Piece, _for_strike: bool) -> &'static [i8] {
+        if piece.is_king { &ALL_DIRECTIONS } else { forward(piece.color) }
+    }
+
+    fn flying_kings(&self) -> bool { false }
+
+    fn promotion(&self) -> Promotion { Promotion::StopsStrike }
+
+    fn first_move(&self) -> Color { Color::Black }
+
+    fn draw_check(&self, history: &mut PositionHistory) -> Option<FinishType> {
+        history.english_draw_check()
+    }
+}
+
+/// Brazilian draughts: International rules on the 8x8 board.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Brazilian;
+
+impl RuleSet for Brazilian {
+    fn promotion(&self) -> Promotion { Promotion::EndOfMove }
+
+    fn filter_strikes(&self, _pos: &Position, list: &mut Vec<MoveItem>) {
+        majority_strikes(list);
+    }
+
+    fn draw_check(&self, history: &mut PositionHistory) -> Option<FinishType> {
+        history.brazilian_draw_check()
+    }
+}
+
+/// Pool checkers: Russian moves, but a man passing the far row during a capture
+/// is crowned only if the move ends there.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pool;
+
+impl RuleSet for Pool {
+    fn promotion(&self) -> Promotion { Promotion::EndOfMove }
+
+    fn draw_check(&self, history: &mut PositionHistory) -> Option<FinishType> {
+        history.pool_draw_check()
+    }
+}
+
+/// Italian draughts: mirrored board, short kings, men move and capture forward only
+/// and can't take kings. The capture is chosen by `italian_strikes` priority.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Italian;
+
+impl RuleSet for Italian {
+    fn directions(&self, piece: &Piece, _for_strike: bool) -> &'static [i8] {
+        if piece.is_king { &ALL_DIRECTIONS } else { forward(piece.color) }
+    }
+
+    fn flying_kings(&self) -> bool { false }
+
+    fn promotion(&self) -> Promotion { Promotion::StopsStrike }
+
+    fn can_take(&self, piece: &Piece, candidate: &Piece) -> bool {
+        piece.is_king || !candidate.is_king
+    }
+
+    fn mirrored_board(&self) -> bool { true }
+
+    fn filter_strikes(&self, pos: &Position, list: &mut Vec<MoveItem>) {
+        italian_strikes(pos, list);
+    }
+
+    fn draw_check(&self, history: &mut PositionHistory) -> Option<FinishType> {
+        history.english_draw_check()
+    }
+}
+
+/// Turkish draughts: all 64 squares, men move and capture forward and sideways, flying kings
+/// move along rows and columns. Taken pieces are removed at once, the capture taking the most
+/// pieces is mandatory and a man is crowned when the move ends.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Turkish;
+
+impl RuleSet for Turkish {
+    fn directions(&self, piece: &Piece, _for_strike: bool) -> &'static [i8] {
+        if piece.is_king { &ORTHOGONAL } else if piece.color == Color::White { &WHITE_ORTHOGONAL } else { &BLACK_ORTHOGONAL }
+    }
+
+    fn promotion(&self) -> Promotion { Promotion::EndOfMove }
+
+    fn full_board(&self) -> bool { true }
+
+    fn board_directions(&self) -> &'static [i8] { &ORTHOGONAL }
+
+    fn start_rows(&self, _size: BoardPos) -> Range<BoardPos> { 1..3 }
+
+    fn removes_at_once(&self) -> bool { true }
+
+    fn filter_strikes(&self, _pos: &Position, list: &mut Vec<MoveItem>) {
+        majority_strikes(list);
+    }
+
+    fn draw_check(&self, history: &mut PositionHistory) -> Option<FinishType> {
+        history.repetition_draw_check()
+    }
+}
+
+/// Frisian draughts: International board and promotion, every piece also captures along rows
+/// and columns, the capture of the highest value is mandatory and a king may make only three
+/// quiet moves in a row while its side still has men.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Frisian;
+
+impl RuleSet for Frisian {
+    fn directions(&self, piece: &Piece, for_strike: bool) -> &'static [i8] {
+        if for_strike { &DIAGONAL_AND_ORTHOGONAL } else if piece.is_king { &ALL_DIRECTIONS } else { forward(piece.color) }
+    }
+
+    fn promotion(&self) -> Promotion { Promotion::EndOfMove }
+
+    fn board_directions(&self) -> &'static [i8] { &DIAGONAL_AND_ORTHOGONAL }
+
+    fn filter_strikes(&self, pos: &Position, list: &mut Vec<MoveItem>) {
+        frisian_strikes(pos, list);
+    }
+
+    fn filter_moves(&self, pos: &Position, list: &mut Vec<MoveItem>) {
+        let color = pos.next_move.unwrap();
+        if pos.state.king_moves(color) < FRISIAN_KING_MOVES || pos.state.count(color).simple == 0 { return; }
+        let man_move = |x: &MoveItem| pos.cells[x.from()].as_ref().is_some_and(|piece| !piece.is_king);
+        // kings may go on only if no man can move
+        if list.iter().any(man_move) { list.retain(man_move); }
+    }
+
+    fn king_moves_limit(&self) -> u8 { FRISIAN_KING_MOVES }
+
+    fn draw_check(&self, history: &mut PositionHistory) -> Option<FinishType> {
+        history.frisian_draw_check()
+    }
+}
+
+const FRISIAN_KING_MOVES: u8 = 3;
+// a king is worth a bit less than two men
+const FRISIAN_MAN_VALUE: u32 = 10;
+const FRISIAN_KING_VALUE: u32 = 19;
+
+/// Frisian capture priority: the highest value taken, then a capture with a king.
+pub fn frisian_strikes(pos: &Position, list: &mut Vec<MoveItem>) {
+    let is_king = |i: BoardPos| pos.cells[i].as_ref().is_some_and(|piece| piece.is_king);
+    let priority = |x: &MoveItem| {
+        let value: u32 = x.strike.as_ref().unwrap().vec.iter()
+            .map(|straight| if is_king(straight.take) { FRISIAN_KING_VALUE } else { FRISIAN_MAN_VALUE })
+            .sum();
+        (value, is_king(x.from()))
+    };
+    let best = list.iter().map(priority).max();
+    list.retain(|x| Some(priority(x)) == best);
+}
+
+/// Antidraughts (giveaway): Russian moves and captures, the side that loses all its pieces
+/// or gets blocked wins. Only repetition draws the game.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Antidraughts;
+
+impl RuleSet for Antidraughts {
+    fn losing(&self) -> bool { true }
+
+    fn draw_check(&self, history: &mut PositionHistory) -> Option<FinishType> {
+        history.repetition_draw_check()
+    }
+}
+
+/// Canadian draughts: International rules on the 12x12 board with 30 men a side.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Canadian;
+
+impl RuleSet for Canadian {
+    fn promotion(&self) -> Promotion { Promotion::EndOfMove }
+
+    fn filter_strikes(&self, _pos: &Position, list: &mut Vec<MoveItem>) {
+        majority_strikes(list);
+    }
+
+    fn draw_check(&self, history: &mut PositionHistory) -> Option<FinishType> {
+        history.fmjd_draw_check()
+    }
+}
+
+/// Keeps only the captures that take the most pieces.
+pub fn majority_strikes(list: &mut Vec<MoveItem>) {
+    let taken = |x: &MoveItem| x.strike.as_ref().map_or(0, |strike| strike.vec.len());
+    let max = list.iter().map(taken).max().unwrap_or(0);
+    list.retain(|x| taken(x) == max);
+}
+
+/// Italian capture priority, each rule deciding only between captures equal by the previous ones:
+/// most pieces taken, capture with a king, most kings taken, a king taken earliest.
+pub fn italian_strikes(pos: &Position, list: &mut Vec<MoveItem>) {
+    let is_king = |i: BoardPos| pos.cells[i].as_ref().is_some_and(|piece| piece.is_king);
+    let priority = |x: &MoveItem| {
+        let strike = x.strike.as_ref().unwrap();
+        let kings: Vec<usize> = strike.vec.iter().enumerate()
+            .filter(|(_, straight)| is_king(straight.take))
+            .map(|(i, _)| i).collect();
+        (strike.vec.len(), is_king(x.from()), kings.len(), Reverse(kings.first().copied().unwrap_or(usize::MAX)))
+    };
+    let best = list.iter().map(priority).max();
+    list.retain(|x| Some(priority(x)) == best);
+}
+
+pub fn default_rules() -> Arc<dyn RuleSet> {
+    Arc::new(Russian)
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum Variant {
+    Russian,
+    International,
+    English,
+    Brazilian,
+    Pool,
+    Italian,
+    Turkish,
+    Frisian,
+    Antidraughts,
+    Canadian,
+}
+
+impl Variant {
+    pub fn rules(&self) -> Arc<dyn RuleSet> {
+        match self {
+            Variant::Russian => Arc::new(Russian),
+            Variant::International => Arc::new(International),
+            Variant::English => Arc::new(English),
+            Variant::Brazilian => Arc::new(Brazilian),
+            Variant::Pool => Arc::new(Pool),
+            Variant::Italian => Arc::new(Italian),
+            Variant::Turkish => Arc::new(Turkish),
+            Variant::Frisian => Arc::new(Frisian),
+            Variant::Antidraughts => Arc::new(Antidraughts),
+            Variant::Canadian => Arc::new(Canadian),
+        }
+    }
+
+    pub fn size(&self) -> i8 {
+        match self {
+            Variant::Russian | Variant::English | Variant::Brazilian | Variant::Pool
+            | Variant::Italian | Variant::Turkish | Variant::Antidraughts => 8,
+            Variant::International | Variant::Frisian => 10,
+            Variant::Canadian => 12,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::color::Color;
+    use crate::game::Game;
+    use crate::moves::BoardPos;
+    use crate::piece::Piece;
+    use crate::position::Position;
+    use crate::position_environment::PositionEnvironment;
+    use crate::PositionHistory::{FinishType, PositionHistory};
+    use crate::PositionHistory::FinishType::{Draw1, Draw2};
+    use crate::rules::{RuleSet, Promotion, Variant};
+
+    #[derive(Debug)]
+    struct ShortKings;
+
+    impl RuleSet for ShortKings {
+        fn flying_kings(&self) -> bool { false }
+        fn promotion(&self) -> Promotion { Promotion::EndOfMove }
+        fn draw_check(&self, _history: &mut PositionHistory) -> Option<FinishType> { None }
+    }
+
+    #[test]
+    fn russian_by_default() {
+        let game = Game::new(8);
+        let rules = &game.current_position.environment.rules;
+        assert!(rules.flying_kings());
+        assert_eq!(rules.promotion(), Promotion::MidStrike);
+    }
+
+    #[test]
+    fn custom_rules() {
+        let environment = Arc::new(PositionEnvironment::with_rules(8, Arc::new(ShortKings)));
+        let mut pos = Position::new(environment.clone());
+        pos.next_move = Some(Color::White);
+        pos.insert_piece(Piece::new(environment.board_to_pack[0], Color::White, true));
+        // a short king in the corner has one move instead of the whole main road
+        assert_eq!(pos.get_move_list(false).list.len(), 1);
+    }
+
+    #[test]
+    fn promotion_timing() {
+        let strike_from_b6 = |environment: Arc<PositionEnvironment>| {
+            let mut pos = Position::new(environment.clone());
+            pos.next_move = Some(Color::White);
+            pos.insert_piece(Piece::new(environment.board_to_pack[41], Color::White, false));
+            vec![50, 52].iter()
+                .for_each(|x| pos.insert_piece(Piece::new(environment.board_to_pack[*x], Color::Black, false)));
+            pos.get_move_list(false)
+        };
+        // Russian: crowned on d8, the new king takes e7 and may stop on f6, g5 or h4
+        let list = strike_from_b6(Arc::new(PositionEnvironment::new(8)));
+        assert_eq!(list.list.len(), 3);
+        assert!(list.list.iter().all(|x| x.is_king_move()));
+        // end of move promotion: the man goes on as a man and is not crowned on f6
+        let list = strike_from_b6(Arc::new(PositionEnvironment::with_rules(8, Arc::new(ShortKings))));
+        assert_eq!(list.list.len(), 1);
+        assert!(!list.list[0].is_king_move());
+    }
+
+    #[test]
+    fn international_start() {
+        let mut game = Game::new_standard(Variant::International);
+        let state = &game.current_position.state;
+        assert_eq!((state.white.simple, state.black.simple), (20, 20));
+        assert_eq!(game.current_position.get_move_list(false).list.len(), 9);
+    }
+
+    #[test]
+    fn international_majority_capture() {
+        let mut game = Game::new_variant(Variant::International);
+        game.current_position.next_move = Some(Color::White);
+        // c3 can take d4, g1 can take h2 and then h4
+        game.insert_piece(Piece::new(game.to_pack(22), Color::White, false));
+        game.insert_piece(Piece::new(game.to_pack(6), Color::White, false));
+        vec![33, 17, 37].iter()
+            .for_each(|pos| game.insert_piece(Piece::new(game.to_pack(*pos), Color::Black, false)));
+        let list = game.current_position.get_move_list(false);
+        assert_eq!(list.list.len(), 1);
+        assert_eq!(list.list[0].strike.as_ref().unwrap().vec.len(), 2);
+        assert_eq!(game.to_board(list.list[0].from()), 6);
+    }
+
+    #[test]
+    fn fmjd_repetition() {
+        let mut game = Game::new_variant(Variant::International);
+        game.current_position.next_move = Some(Color::White);
+        game.insert_piece(Piece::new(game.to_pack(0), Color::White, true));
+        game.insert_piece(Piece::new(game.to_pack(97), Color::Black, true));
+        let moves = [(0, 11), (97, 86), (11, 0), (86, 97)];
+        for ply in 0..9 {
+            let (from, to) = moves[ply % 4];
+            let (from, to) = (game.to_pack(from), game.to_pack(to));
+            let list = game.current_position.get_move_list(false);
+            let mov = list.list.iter().find(|x| x.from() == from && x.to() == to).unwrap();
+            let finish = game.make_move_by_move_item(mov);
+            if ply < 8 { assert!(finish.is_none()) } else { assert!(matches!(finish, Some(Draw2))) }
+        }
+    }
+
+    #[test]
+    fn english_moves() {
+        let mut game = Game::new_variant(Variant::English);
+        assert_eq!(game.current_position.environment.rules.first_move(), Color::Black);
+        game.current_position.next_move = Some(Color::White);
+        // short king on a1 and a man on d4 that can't take c3 backwards
+        game.insert_piece(Piece::new(game.to_pack(0), Color::White, true));
+        game.insert_piece(Piece::new(game.to_pack(27), Color::White, false));
+        game.insert_piece(Piece::new(game.to_pack(18), Color::Black, false));
+        let list = game.current_position.get_move_list(false);
+        assert!(list.list.iter().all(|x| x.mov.is_some()));
+        assert_eq!(list.list.len(), 3);
+    }
+
+    #[test]
+    fn english_crowning_ends_strike() {
+        let mut game = Game::new_variant(Variant::English);
+        game.current_position.next_move = Some(Color::White);
+        game.insert_piece(Piece::new(game.to_pack(41), Color::White, false));
+        vec![50, 52].iter()
+            .for_each(|pos| game.insert_piece(Piece::new(game.to_pack(*pos), Color::Black, false)));
+        let list = game.current_position.get_move_list(false);
+        assert_eq!(list.list.len(), 1);
+        assert_eq!(list.list[0].strike.as_ref().unwrap().vec.len(), 1);
+        assert!(list.list[0].is_king_move());
+    }
+
+    #[test]
+    fn english_forty_moves() {
+        let mut game = Game::new_variant(Variant::English);
+        game.current_position.next_move = Some(Color::White);
+        game.insert_piece(Piece::new(game.to_pack(9), Color::White, true));
+        game.insert_piece(Piece::new(game.to_pack(34), Color::Black, true));
+        // kings walk around cycles of 4 and 10 squares, so no position comes three times in 80 moves
+        let white = [9, 18, 11, 2];
+        let black = [34, 43, 36, 45, 54, 61, 52, 59, 50, 41];
+        for ply in 0..80 {
+            let (from, to) = if ply % 2 == 0 {
+                (white[ply / 2 % 4], white[(ply / 2 + 1) % 4])
+            } else {
+                (black[ply / 2 % 10], black[(ply / 2 + 1) % 10])
+            };
+            let (from, to) = (game.to_pack(from), game.to_pack(to));
+            let list = game.current_position.get_move_list(false);
+            let mov = list.list.iter().find(|x| x.from() == from && x.to() == to).unwrap();
+            let finish = game.make_move_by_move_item(mov);
+            if ply < 79 { assert!(finish.is_none()) } else { assert!(matches!(finish, Some(Draw1))) }
+        }
+    }
+
+    fn strike_game(variant: Variant, white: (BoardPos, bool), black: &[BoardPos]) -> Game {
+        let mut game = Game::new_variant(variant);
+        game.current_position.next_move = Some(Color::White);
+        game.insert_piece(Piece::new(game.to_pack(white.0), Color::White, white.1));
+        black.iter().for_each(|pos| game.insert_piece(Piece::new(game.to_pack(*pos), Color::Black, false)));
+        game
+    }
+
+    #[test]
+    fn king_strike_conformance() {
+        // the king on a1 against nine men, "one of 42 strike variants" from the readme
+        let black = [9, 11, 13, 25, 27, 29, 41, 43, 45];
+        for (variant, count, min_taken) in [
+            (Variant::Russian, 42, 3), (Variant::Pool, 42, 3),
+            (Variant::Brazilian, 12, 9), (Variant::English, 9, 3)] {
+            let mut game = strike_game(variant, (0, true), &black);
+            let list = game.current_position.get_move_list(false);
+            assert_eq!(list.list.len(), count, "{:?}", variant);
+            let taken = list.list.iter().map(|x| x.strike.as_ref().unwrap().vec.len()).min();
+            assert_eq!(taken, Some(min_taken), "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn man_strike_conformance() {
+        // b6 takes c7 and reaches d8 in the middle of the capture
+        let black = [50, 52, 38, 18];
+        for variant in [Variant::Russian, Variant::Pool, Variant::Brazilian, Variant::English] {
+            let mut game = strike_game(variant, (41, false), &black);
+            let list = game.current_position.get_move_list(false);
+            let ends: Vec<_> = list.list.iter().map(|x| (game.to_board(x.to()), x.is_king_move())).collect();
+            match variant {
+                // crowned on d8, the king goes on through e7 and takes g5 or c3
+                Variant::Russian => {
+                    assert_eq!(ends.len(), 3);
+                    assert!(ends.iter().all(|x| x.1));
+                }
+                // still a man on d8, it takes e7 and g5 backwards and stays a man
+                Variant::Pool | Variant::Brazilian => assert_eq!(ends, vec![(31, false)]),
+                // crowning ends the move
+                _ => assert_eq!(ends, vec![(59, true)]),
+            }
+        }
+    }
+
+    fn italian_game(white: &[(BoardPos, bool)], black: &[(BoardPos, bool)]) -> Game {
+        let mut game = Game::new_variant(Variant::Italian);
+        game.current_position.next_move = Some(Color::White);
+        for (color, pieces) in [(Color::White, white), (Color::Black, black)] {
+            pieces.iter().for_each(|(pos, is_king)| game.insert_piece(Piece::new(game.to_pack(*pos), color, *is_king)));
+        }
+        game
+    }
+
+    fn move_ends(game: &mut Game) -> Vec<BoardPos> {
+        let list = game.current_position.get_move_list(false);
+        let mut ends: Vec<_> = list.list.iter().map(|x| game.to_board(x.to())).collect();
+        ends.sort();
+        ends
+    }
+
+    #[test]
+    fn italian_board() {
+        let mut game = Game::new_variant(Variant::Italian);
+        let environment = game.current_position.environment.clone();
+        // b1 is the first dark square, h1 is dark in the right hand corner
+        assert_eq!(environment.pack_to_board[0], 1);
+        assert_eq!(environment.pack_to_board[3], 7);
+        game.current_position = Position::initial(environment);
+        let state = &game.current_position.state;
+        assert_eq!((state.white.simple, state.black.simple), (12, 12));
+        assert_eq!(game.current_position.get_move_list(false).list.len(), 7);
+    }
+
+    #[test]
+    fn italian_man_cannot_take_king() {
+        // the man on d3 can't take the king on e4, only move to c4
+        let mut game = italian_game(&[(19, false)], &[(28, true)]);
+        let list = game.current_position.get_move_list(false);
+        assert!(list.list.iter().all(|x| x.mov.is_some()));
+        assert_eq!(move_ends(&mut game), vec![26]);
+    }
+
+    #[test]
+    fn italian_most_pieces() {
+        // d3 takes e4 and g6, d3 takes c4 alone, b3 takes c4 alone
+        let mut game = italian_game(&[(19, false), (17, false)], &[(28, false), (46, false), (26, false)]);
+        assert_eq!(move_ends(&mut game), vec![55]);
+    }
+
+    #[test]
+    fn italian_king_takes_first() {
+        // one piece either way, but the king on d3 must capture instead of the man on b3
+        let mut game = italian_game(&[(19, true), (17, false)], &[(28, false), (26, false)]);
+        let list = game.current_position.get_move_list(false);
+        assert!(list.list.iter().all(|x| game.to_board(x.from()) == 19));
+        assert_eq!(move_ends(&mut game), vec![33, 37]);
+    }
+
+    #[test]
+    fn italian_most_kings() {
+        // the king on d3 takes the king on e4 rather than the man on c4
+        let mut game = italian_game(&[(19, true)], &[(28, true), (26, false)]);
+        assert_eq!(move_ends(&mut game), vec![37]);
+    }
+
+    #[test]
+    fn italian_earliest_king() {
+        // both captures take a man and a king, e4 and g6 meets the king first
+        let mut game = italian_game(&[(19, true)], &[(28, true), (46, false), (26, false), (42, true)]);
+        assert_eq!(move_ends(&mut game), vec![55]);
+    }
+
+    #[test]
+    fn turkish_board() {
+        let mut game = Game::new_variant(Variant::Turkish);
+        let environment = game.current_position.environment.clone();
+        assert_eq!(game.current_position.cells.len(), 64);
+        // a1 goes up and right only, the ban direction of up is down
+        let v = environment.get_vectors(0);
+        assert_eq!(v.iter().map(|x| x.direction).collect::<Vec<_>>(), vec![4, 7]);
+        assert_eq!(*v[0].points, vec![0, 8, 16, 24, 32, 40, 48, 56]);
+        assert_eq!(v[0].get_ban_direction(), 6);
+        assert_eq!(v[1].get_ban_direction(), 5);
+        game.current_position = Position::initial(environment);
+        let state = &game.current_position.state;
+        assert_eq!((state.white.simple, state.black.simple), (16, 16));
+        assert_eq!(game.current_position.get_move_list(false).list.len(), 8);
+    }
+
+    #[test]
+    fn turkish_man() {
+        // d4 moves up, left or right
+        let mut game = Game::new_variant(Variant::Turkish);
+        game.current_position.next_move = Some(Color::White);
+        game.insert_piece(Piece::new(27, Color::White, false));
+        assert_eq!(move_ends(&mut game), vec![26, 28, 35]);
+        // and takes d5 or c4 but not d3 behind it
+        [35, 26, 19].iter().for_each(|pos| game.insert_piece(Piece::new(*pos, Color::Black, false)));
+        assert_eq!(move_ends(&mut game), vec![25, 43]);
+    }
+
+    #[test]
+    fn turkish_king_strike() {
+        // a1 takes a3, c6, d4 and b3, coming back to a3 which is already free
+        let mut game = Game::new_variant(Variant::Turkish);
+        game.current_position.next_move = Some(Color::White);
+        game.insert_piece(Piece::new(0, Color::White, true));
+        [16, 42, 27, 17].iter().for_each(|pos| game.insert_piece(Piece::new(*pos, Color::Black, false)));
+        let list = game.current_position.get_move_list(false);
+        assert_eq!(list.list.len(), 1);
+        let strike = list.list[0].strike.as_ref().unwrap();
+        let takes: Vec<_> = strike.vec.iter().map(|x| x.take).collect();
+        assert_eq!(takes, vec![16, 42, 27, 17]);
+        assert_eq!(list.list[0].to(), 16);
+        let pos = &mut game.current_position;
+        pos.make_move(&list.list[0]);
+        assert_eq!(pos.state.black.simple, 0);
+        assert!(pos.cells[16].as_ref().is_some_and(|x| x.color == Color::White));
+    }
+
+    fn frisian_game(white: &[(BoardPos, bool)], black: &[(BoardPos, bool)]) -> Game {
+        let mut game = Game::new_variant(Variant::Frisian);
+        game.current_position.next_move = Some(Color::White);
+        for (color, pieces) in [(Color::White, white), (Color::Black, black)] {
+            pieces.iter().for_each(|(pos, is_king)| game.insert_piece(Piece::new(game.to_pack(*pos), color, *is_king)));
+        }
+        game
+    }
+
+    #[test]
+    fn frisian_orthogonal_strike() {
+        // c5 takes c3 backwards along the column, c4 is a light square
+        let mut game = frisian_game(&[(42, false)], &[(22, false)]);
+        assert_eq!(move_ends(&mut game), vec![2]);
+        // a flying king takes along the row from a1 over e1
+        let mut game = frisian_game(&[(0, true)], &[(4, false)]);
+        assert_eq!(move_ends(&mut game), vec![6, 8]);
+    }
+
+    #[test]
+    fn frisian_strike_value() {
+        // the king on a1 can take the king on b2, the man on h4 can take g5 and e7
+        let white = [(0, true), (33, false), (37, false)];
+        let mut game = frisian_game(&white, &[(11, true), (46, false), (64, false)]);
+        // two men are worth more than a king
+        assert_eq!(move_ends(&mut game), vec![73]);
+        // a king is worth more than a man
+        let mut game = frisian_game(&white, &[(11, true), (46, false)]);
+        assert_eq!(move_ends(&mut game), vec![22]);
+        // the same value is taken by the king
+        let mut game = frisian_game(&white, &[(11, false), (46, false)]);
+        assert_eq!(move_ends(&mut game), vec![22]);
+    }
+
+    #[test]
+    fn frisian_king_moves() {
+        let mut game = frisian_game(&[(0, true), (6, false)], &[(95, true)]);
+        let moves = [(0, 11), (95, 86), (11, 0), (86, 95), (0, 11), (95, 86)];
+        for (from, to) in moves {
+            let (from, to) = (game.to_pack(from), game.to_pack(to));
+            let list = game.current_position.get_move_list(false);
+            let mov = list.list.iter().find(|x| x.from() == from && x.to() == to).unwrap();
+            assert!(game.make_move_by_move_item(mov).is_none());
+        }
+        // after three king moves in a row only the man on g1 may move
+        assert_eq!(game.current_position.state.white_king_moves, 3);
+        let list = game.current_position.get_move_list(false);
+        assert_eq!(move_ends(&mut game), vec![15, 17]);
+        // a man move resets the counter and unmake_move brings it back
+        let pos = &mut game.current_position;
+        pos.make_move(&list.list[0]);
+        assert_eq!(pos.state.white_king_moves, 0);
+        pos.unmake_move(&list.list[0]);
+        assert_eq!(pos.state.white_king_moves, 3);
+    }
+
+    #[test]
+    fn antidraughts_finish() {
+        // c3 has to take the last black man, Black is left without pieces and wins
+        let mut game = strike_game(Variant::Antidraughts, (18, false), &[27]);
+        let list = game.current_position.get_move_list(false);
+        assert!(matches!(game.make_move_by_move_item(&list.list[0]), Some(FinishType::BlackWin)));
+        let mut game = strike_game(Variant::Russian, (18, false), &[27]);
+        let list = game.current_position.get_move_list(false);
+        assert!(matches!(game.make_move_by_move_item(&list.list[0]), Some(FinishType::WhiteWin)));
+    }
+
+    #[test]
+    fn antidraughts_evaluate() {
+        for (variant, sign) in [(Variant::Russian, 1), (Variant::Antidraughts, -1)] {
+            let mut game = strike_game(variant, (0, true), &[63]);
+            game.insert_piece(Piece::new(game.to_pack(2), Color::White, false));
+            let pos = &mut game.current_position;
+            assert_eq!(pos.state.evaluate().signum(), sign);
+            assert_eq!(pos.evaluate(true).signum(), sign);
+        }
+    }
+
+    #[test]
+    fn antidraughts_best_move() {
+        // c3-d4 gives the only man away, e5 takes it and White has nothing left
+        let mut game = strike_game(Variant::Antidraughts, (18, false), &[36]);
+        let best = game.best_move(4, i32::MIN / 2, i32::MAX / 2, 0, true);
+        assert_eq!(game.to_board(best.get_move_item().to()), 27);
+    }
+
+    #[test]
+    fn canadian_start() {
+        let mut game = Game::new_standard(Variant::Canadian);
+        let state = &game.current_position.state;
+        assert_eq!((state.white.simple, state.black.simple), (30, 30));
+        // white men on rows 1-5, black men on rows 8-12
+        assert!(game.current_position.cells.iter().flatten().all(|piece| {
+            let row = game.to_board(piece.pos) / 12;
+            if piece.color == Color::White { row < 5 } else { row > 6 }
+        }));
+        assert_eq!(game.current_position.get_move_list(false).list.len(), 11);
+    }
+
+    #[test]
+    fn canadian_majority_capture() {
+        // the man on b2 takes c3 alone, the king on k1 takes j2 and then j6
+        let mut game = strike_game(Variant::Canadian, (10, true), &[21, 69]);
+        game.insert_piece(Piece::new(game.to_pack(13), Color::White, false));
+        game.insert_piece(Piece::new(game.to_pack(26), Color::Black, false));
+        let list = game.current_position.get_move_list(false);
+        assert!(!list.list.is_empty());
+        assert!(list.list.iter().all(|x| x.strike.as_ref().unwrap().vec.len() == 2));
+        assert!(list.list.iter().all(|x| game.to_board(x.from()) == 10));
+    }
+
+    #[test]
+    fn standard_start() {
+        for (variant, men, first) in [
+            (Variant::Russian, 12, Color::White), (Variant::International, 20, Color::White),
+            (Variant::English, 12, Color::Black), (Variant::Brazilian, 12, Color::White),
+            (Variant::Pool, 12, Color::White), (Variant::Italian, 12, Color::White),
+            (Variant::Turkish, 16, Color::White), (Variant::Frisian, 20, Color::White),
+            (Variant::Antidraughts, 12, Color::White), (Variant::Canadian, 30, Color::White)] {
+            let mut game = Game::new_standard(variant);
+            let pos = &mut game.current_position;
+            assert_eq!((pos.state.white.simple, pos.state.black.simple), (men, men), "{:?}", variant);
+            assert_eq!(pos.next_move, Some(first), "{:?}", variant);
+            assert!(!pos.get_move_list(false).list.is_empty(), "{:?}", variant);
+        }
+        // the old hand made layout of main.rs
+        let white = [0, 2, 4, 6, 9, 11, 13, 15, 16, 18, 20, 22];
+        let game = Game::new_standard(Variant::Russian);
+        for pos in white {
+            let piece = game.current_position.cells[game.to_pack(pos)].as_ref().unwrap();
+            assert_eq!(piece.color, Color::White);
+            let piece = game.current_position.cells[game.to_pack(63 - pos)].as_ref().unwrap();
+            assert_eq!(piece.color, Color::Black);
+        }
+    }
+
+    #[test]
+    fn select_variant() {
+        let mut game = Game::new(8);
+        game.set_variant(Variant::Brazilian);
+        assert_eq!(game.current_position.environment.rules.promotion(), Promotion::EndOfMove);
+        game.set_variant(Variant::International);
+        assert_eq!(game.current_position.environment.size, 10);
+        assert_eq!(game.current_position.cells.len(), 50);
+    }
+}
@@ -0,0 +1,380 @@
+use std::mem::take;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use wasm_bindgen::JsValue;
+use crate::game::Game;
+use crate::moves_list::MoveItem;
+use crate::transposition::TranspositionTable;
+use crate::zobrist::MAX_CELLS;
+
+/// Deepest iteration when only the time or the nodes limit the search.
+const MAX_DEPTH: i16 = 64;
+
+// the clock is read once in so many nodes
+const CLOCK_EVERY: u64 = 256;
+
+// history scores are halved when one of them gets this big
+const HISTORY_MAX: u32 = 1 << 24;
+
+/// When `Game::search` stops, the first limit reached counts.
+/// Without any limit it searches to the depth set by `Game::set_depth`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SearchLimits {
+    pub depth: Option<i16>,
+    pub time_ms: Option<f64>,
+    #[ts(type = "number | null")]
+    pub nodes: Option<u64>,
+    /// number of the best root moves to find, each with its exact score and line
+    pub multi_pv: Option<u16>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: i16) -> SearchLimits {
+        SearchLimits { depth: Some(depth), ..SearchLimits::default() }
+    }
+
+    pub fn time_ms(time_ms: f64) -> SearchLimits {
+        SearchLimits { time_ms: Some(time_ms), ..SearchLimits::default() }
+    }
+
+    pub fn nodes(nodes: u64) -> SearchLimits {
+        SearchLimits { nodes: Some(nodes), ..SearchLimits::default() }
+    }
+
+    pub fn with_multi_pv(self, lines: u16) -> SearchLimits {
+        SearchLimits { multi_pv: Some(lines), ..self }
+    }
+}
+
+/// A root move with its score and the moves expected after it, the move first.
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
+#[ts(export)]
+pub struct PvLine {
+    pub score: i32,
+    pub pv: Vec<MoveItem>,
+}
+
+/// Best move of the last completed iteration with its score, White's advantage if positive.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct SearchResult {
+    pub mov: Option<MoveItem>,
+    pub score: i32,
+    /// principal variation, starting with `mov`
+    pub pv: Vec<MoveItem>,
+    /// the best root moves, best first, for multi-PV; the main line alone otherwise
+    pub lines: Vec<PvLine>,
+    pub depth: i16,
+    #[ts(type = "number")]
+    pub nodes: u64,
+    /// nodes cut off by a move, by the first move searched and by the transposition table
+    #[ts(type = "number")]
+    pub cutoffs: u64,
+    #[ts(type = "number")]
+    pub first_move_cutoffs: u64,
+    #[ts(type = "number")]
+    pub tt_cutoffs: u64,
+    pub time_ms: f64,
+}
+
+// milliseconds from a fixed moment; Instant is not available in the browser
+#[cfg(target_arch = "wasm32")]
+fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+/// Budget and counters of the running search, `best_move` counts its nodes here.
+#[derive(Debug, Default)]
+pub(crate) struct SearchState {
+    limits: SearchLimits,
+    started_ms: f64,
+    pub(crate) nodes: u64,
+    // the first iteration always completes, so there is a move to return
+    can_stop: bool,
+    pub(crate) stopped: bool,
+    // best move of the last completed iteration, searched first in the next one
+    pub(crate) best_move: Option<MoveItem>,
+    // root moves left out of the search, the lines multi-PV has found
+    pub(crate) excluded: Vec<MoveItem>,
+    // two quiet moves a ply that cut off the search last
+    killers: Vec<[Option<MoveItem>; 2]>,
+    // quiet cutoffs by the squares of the move, from * MAX_CELLS + to
+    history: Vec<u32>,
+    pub(crate) cutoffs: u64,
+    pub(crate) first_move_cutoffs: u64,
+    pub(crate) tt_cutoffs: u64,
+}
+
+impl SearchState {
+    fn start(limits: SearchLimits) -> SearchState {
+        SearchState { limits, started_ms: now_ms(), ..SearchState::default() }
+    }
+
+    fn elapsed_ms(&self) -> f64 {
+        now_ms() - self.started_ms
+    }
+
+    /// Order of the moves at the ply, the biggest first: the move of the table or the previous
+    /// iteration, captures by the pieces taken, the killers, then the quiet moves by their history.
+    pub(crate) fn order_key(&self, mov: &MoveItem, ply: i16, first: Option<&MoveItem>) -> u64 {
+        if first == Some(mov) { return u64::MAX; }
+        if let Some(strike) = &mov.strike { return (1 << 40) + strike.vec.len() as u64; }
+        let killers = self.killers.get(ply as usize);
+        match killers.and_then(|killers| killers.iter().position(|x| x.as_ref() == Some(mov))) {
+            Some(i) => (1 << 32) - i as u64,
+            None => self.history.get(mov.from() * MAX_CELLS + mov.to()).copied().unwrap_or(0) as u64,
+        }
+    }
+
+    /// The move at the ply cut off the search of `depth` plies.
+    pub(crate) fn cutoff(&mut self, mov: &MoveItem, ply: i16, depth: i16, first: bool) {
+        self.cutoffs += 1;
+        if first { self.first_move_cutoffs += 1; }
+        // the captures are searched first anyway
+        if mov.strike.is_some() { return; }
+        let ply = ply as usize;
+        if self.killers.len() <= ply { self.killers.resize(ply + 1, [None, None]); }
+        let killers = &mut self.killers[ply];
+        if killers[0].as_ref() != Some(mov) {
+            killers[1] = killers[0].replace(mov.clone());
+        }
+        if self.history.is_empty() { self.history = vec![0; MAX_CELLS * MAX_CELLS]; }
+        let score = &mut self.history[mov.from() * MAX_CELLS + mov.to()];
+        *score += (depth.max(1) as u32).pow(2);
+        if *score >= HISTORY_MAX {
+            self.history.iter_mut().for_each(|x| *x /= 2);
+        }
+    }
+
+    /// Counts the node, false once the budget has run out.
+    pub(crate) fn visit(&mut self) -> bool {
+        if self.stopped { return false; }
+        self.nodes += 1;
+        if self.can_stop {
+            self.stopped = self.limits.nodes.is_some_and(|nodes| self.nodes > nodes)
+                || (self.nodes.is_multiple_of(CLOCK_EVERY)
+                && self.limits.time_ms.is_some_and(|time_ms| self.elapsed_ms() >= time_ms));
+        }
+        !self.stopped
+    }
+}
+
+impl Game {
+    /// Iterative deepening: searches one ply deeper at a time, starting with the best move
+    /// found so far, until a limit is reached. An unfinished iteration is thrown away.
+    pub fn search(&mut self, limits: SearchLimits) -> SearchResult {
+        let unlimited = limits.time_ms.is_none() && limits.nodes.is_none();
+        let max_depth = limits.depth.unwrap_or(if unlimited { self.max_depth } else { MAX_DEPTH });
+        self.search = SearchState::start(limits);
+        // the entries of the passes and the lines of this search stay young
+        self.tt.new_search();
+        let mut result = SearchResult {
+            mov: None, score: 0, pv: vec![], lines: vec![], depth: 0,
+            nodes: 0, cutoffs: 0, first_move_cutoffs: 0, tt_cutoffs: 0, time_ms: 0.0,
+        };
+        let finished = self.position_history.borrow_mut().finish_check().is_some();
+        let moves = self.current_position.get_move_list_cached().as_ref().as_ref().unwrap().list.len();
+        let multi_pv = (limits.multi_pv.unwrap_or(1).max(1) as usize).min(moves);
+        if !finished && moves > 0 {
+            'deepening: for depth in 1..=max_depth.max(1) {
+                // every next line is the best of the moves left, searched in the full window for the exact score
+                let mut lines = vec![];
+                while lines.len() < multi_pv {
+                    let best = self.best_move(depth, i32::MIN / 2, i32::MAX / 2, 0, false);
+                    if self.search.stopped { break 'deepening; }
+                    self.search.excluded.push(best.get_move_item());
+                    lines.push(PvLine { score: best.deep_eval, pv: best.pv });
+                }
+                self.search.excluded.clear();
+                (result.mov, result.score, result.pv) = (lines[0].pv.first().cloned(), lines[0].score, lines[0].pv.clone());
+                (result.lines, result.depth) = (lines, depth);
+                self.search.best_move = result.mov.clone();
+                self.search.can_stop = true;
+                if limits.time_ms.is_some_and(|time_ms| self.search.elapsed_ms() >= time_ms) { break; }
+            }
+        }
+        (result.nodes, result.time_ms) = (self.search.nodes, self.search.elapsed_ms());
+        (result.cutoffs, result.first_move_cutoffs, result.tt_cutoffs) =
+            (self.search.cutoffs, self.search.first_move_cutoffs, self.search.tt_cutoffs);
+        self.search = SearchState::default();
+        result
+    }
+
+    /// Line of the moves stored in the transposition table, at most `depth` of them.
+    pub(crate) fn tt_line(&mut self, depth: i16, state_only: bool) -> Vec<MoveItem> {
+        let mut line: Vec<(MoveItem, Vec<_>)> = vec![];
+        while line.len() < depth.max(0) as usize {
+            let key = TranspositionTable::key(self.current_position.hash(), state_only);
+            let mov = self.tt.probe(key).and_then(|entry| entry.mov.clone());
+            // a move of another position with the same slot is not played
+            let Some(mov) = mov.filter(|mov| self.current_position.get_move_list(false).list.contains(mov)) else { break };
+            self.current_position.make_move(&mov);
+            let took_pieces = take(&mut self.current_position.took_pieces);
+            line.push((mov, took_pieces));
+        }
+        let mut pv = vec![];
+        while let Some((mov, took_pieces)) = line.pop() {
+            self.current_position.took_pieces = took_pieces;
+            self.current_position.unmake_move(&mov);
+            pv.push(mov);
+        }
+        pv.reverse();
+        pv
+    }
+
+    pub fn search_ts_n(&mut self, limits: JsValue) -> Result<JsValue, JsValue> {
+        let limits: SearchLimits = serde_wasm_bindgen::from_value(limits)?;
+        Ok(serde_wasm_bindgen::to_value(&self.search(limits))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Game;
+    use crate::rules::Variant;
+    use crate::search::{SearchLimits, SearchState};
+
+    #[test]
+    fn depth_limit() {
+        let mut game = Game::new_standard(Variant::Russian);
+        let result = game.search(SearchLimits::depth(4));
+        assert_eq!(result.depth, 4);
+        let mov = result.mov.unwrap();
+        assert!(game.current_position.get_move_list(false).list.contains(&mov));
+        // without limits the game's depth is used
+        game.set_depth(2);
+        assert_eq!(game.search(SearchLimits::default()).depth, 2);
+    }
+
+    #[test]
+    fn node_and_time_limits() {
+        let mut game = Game::new_standard(Variant::International);
+        let result = game.search(SearchLimits::nodes(3000));
+        assert!(result.depth >= 1 && result.mov.is_some());
+        // the unfinished iteration may go a bit over before it notices
+        assert!(result.nodes <= 3001, "{}", result.nodes);
+        let result = game.search(SearchLimits::time_ms(50.0));
+        assert!(result.depth >= 1 && result.mov.is_some());
+        assert!(result.time_ms < 1000.0, "{}", result.time_ms);
+    }
+
+    #[test]
+    fn the_king_takes_all() {
+        let mut game = Game::new_variant(Variant::Russian);
+        game.set_fen("W:WK29:B9,10,11,17,18,19,25,26,27").unwrap();
+        let result = game.search(SearchLimits::depth(2));
+        assert_eq!(result.mov.unwrap().strike.unwrap().vec.len(), 9);
+    }
+
+    #[test]
+    fn principal_variation() {
+        for tt_size in [0, 1 << 12] {
+            let mut game = Game::new_standard(Variant::Russian);
+            game.set_tt_size(tt_size);
+            game.make_move_by_notation("c3-d4").unwrap();
+            let best = game.best_move(4, i32::MIN / 2, i32::MAX / 2, 0, true);
+            assert_eq!(best.pv[0], best.get_move_item());
+            let mut pos = game.current_position.clone();
+            for mov in &best.pv {
+                assert!(pos.get_move_list(false).list.contains(mov), "{:?}", mov);
+                pos.make_move(mov);
+            }
+            if tt_size == 0 {
+                // the score is the one of the position the line leads to
+                assert!(best.pv.len() >= 4);
+                assert_eq!(pos.quiescence(i32::MIN / 2, i32::MAX / 2, true), best.deep_eval);
+            }
+        }
+    }
+
+    #[test]
+    fn multi_pv() {
+        let mut game = Game::new_standard(Variant::Russian);
+        let result = game.search(SearchLimits::depth(4).with_multi_pv(3));
+        assert_eq!(result.lines.len(), 3);
+        assert_eq!(result.pv, result.lines[0].pv);
+        assert_eq!(result.score, result.lines[0].score);
+        let list = game.current_position.get_move_list(false).list;
+        let first: Vec<_> = result.lines.iter().map(|line| line.pv[0].clone()).collect();
+        assert!(first.iter().all(|mov| list.contains(mov)));
+        assert!(first[0] != first[1] && first[1] != first[2] && first[0] != first[2]);
+        // no more lines than moves
+        game.set_fen("W:W9:B6,7").unwrap();
+        let result = game.search(SearchLimits::depth(2).with_multi_pv(5));
+        assert_eq!(result.lines.len(), 3);
+    }
+
+    #[test]
+    fn table_ages_once_a_search() {
+        let mut game = Game::new_standard(Variant::Russian);
+        let age = game.tt.age();
+        game.search(SearchLimits::depth(4).with_multi_pv(3));
+        assert_eq!(game.tt.age(), age.wrapping_add(1));
+        // the root of the last pass keeps its move for the next search
+        let entry = game.tt.probe(game.current_position.hash()).unwrap();
+        assert!(entry.depth == 4 && entry.mov.is_some());
+    }
+
+    #[test]
+    fn stopped_pass_cuts_nothing() {
+        let mut game = Game::new_standard(Variant::Russian);
+        game.search = SearchState::start(SearchLimits::nodes(1));
+        game.search.can_stop = true;
+        // in a null window any real score of a child would cut off
+        let best = game.best_move(3, -1, 1, 0, false);
+        assert!(game.search.stopped);
+        assert!(best.pv.is_empty());
+        // white to move, nothing searched took the place of the worst score
+        assert_eq!(best.deep_eval, i32::MIN / 2);
+        assert_eq!(game.search.cutoffs, 0);
+        assert!(game.search.killers.is_empty());
+    }
+
+    #[test]
+    fn move_order() {
+        let mut state = SearchState::default();
+        let mut game = Game::new_variant(Variant::Russian);
+        game.set_fen("W:WK29:B9,10,11,17,18,19,25,26,27").unwrap();
+        let captures = game.current_position.get_move_list(false).list;
+        let most = captures.iter().max_by_key(|x| x.strike.as_ref().unwrap().vec.len()).unwrap();
+        assert!(captures.iter().all(|x| state.order_key(most, 0, None) >= state.order_key(x, 0, None)));
+        let quiet = Game::new_standard(Variant::Russian).current_position.get_move_list(false).list;
+        let (a, b, c) = (&quiet[0], &quiet[1], &quiet[2]);
+        state.cutoff(a, 2, 3, true);
+        state.cutoff(b, 2, 1, false);
+        // b is the newer killer at ply 2, a has the better history elsewhere
+        assert!(state.order_key(b, 2, None) > state.order_key(a, 2, None));
+        assert!(state.order_key(a, 2, None) > state.order_key(c, 2, None));
+        assert!(state.order_key(a, 5, None) > state.order_key(b, 5, None));
+        assert!(state.order_key(b, 5, None) > state.order_key(c, 5, None));
+        assert!(state.order_key(c, 2, Some(c)) > state.order_key(b, 2, None));
+        assert_eq!((state.cutoffs, state.first_move_cutoffs), (2, 1));
+    }
+
+    #[test]
+    fn statistics() {
+        let mut game = Game::new_standard(Variant::International);
+        let result = game.search(SearchLimits::depth(4));
+        assert!(result.cutoffs > 0 && result.first_move_cutoffs <= result.cutoffs);
+        assert!(result.nodes > result.cutoffs + result.tt_cutoffs);
+    }
+
+    #[test]
+    fn finished_game() {
+        let mut game = Game::new_variant(Variant::Russian);
+        game.set_fen("W:WK29:B").unwrap();
+        game.set_color(crate::color::Color::Black);
+        let result = game.search(SearchLimits::time_ms(10.0));
+        assert!(result.mov.is_none());
+        assert_eq!(result.depth, 0);
+    }
+}
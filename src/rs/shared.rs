@@ -0,0 +1,122 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use ts_rs::{Dependency, TS};
+
+/// Shared mutable value that can be sent to another thread: `Rc<RefCell<T>>` on an `Arc<RwLock<T>>`.
+/// `borrow` and `borrow_mut` keep the `RefCell` names and panic on a conflicting borrow like it does.
+///
+/// It is `Send + Sync` so that a game or a tree can be handed over to another thread, not so
+/// that threads work on it together: the values reachable from one owner are borrowed by one
+/// thread at a time. A borrow conflicting with one held on another thread panics, it does not wait.
+pub struct Shared<T>(Arc<RwLock<T>>);
+
+impl<T> Shared<T> {
+    pub fn new(value: T) -> Shared<T> {
+        Shared(Arc::new(RwLock::new(value)))
+    }
+
+    pub fn borrow(&self) -> RwLockReadGuard<'_, T> {
+        match self.0.try_read() {
+            Ok(value) => value,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("already mutably borrowed"),
+        }
+    }
+
+    pub fn borrow_mut(&self) -> RwLockWriteGuard<'_, T> {
+        match self.0.try_write() {
+            Ok(value) => value,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("already borrowed"),
+        }
+    }
+
+    /// Both point to the same value.
+    pub fn ptr_eq(this: &Shared<T>, other: &Shared<T>) -> bool {
+        Arc::ptr_eq(&this.0, &other.0)
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Shared(self.0.clone())
+    }
+}
+
+impl<T: Debug> Debug for Shared<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0.try_read() {
+            Ok(value) => value.fmt(f),
+            Err(_) => f.write_str("<borrowed>"),
+        }
+    }
+}
+
+impl<T: Serialize> Serialize for Shared<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.borrow().serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Shared<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Shared::new)
+    }
+}
+
+// the same TypeScript type as T, like ts-rs gives Rc and RefCell
+impl<T: TS> TS for Shared<T> {
+    fn name() -> String { T::name() }
+    fn name_with_type_args(mut args: Vec<String>) -> String {
+        assert_eq!(args.len(), 1);
+        args.remove(0)
+    }
+    fn inline() -> String { T::inline() }
+    fn inline_flattened() -> String { T::inline_flattened() }
+    fn dependencies() -> Vec<Dependency> { T::dependencies() }
+    fn transparent() -> bool { T::transparent() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shared::Shared;
+
+    #[test]
+    fn shared_borrows() {
+        let shared = Shared::new(1);
+        let other = shared.clone();
+        *shared.borrow_mut() += 1;
+        let (a, b) = (shared.borrow(), other.borrow());
+        assert_eq!(*a + *b, 4);
+        assert!(Shared::ptr_eq(&shared, &other));
+    }
+
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn nested_borrow_mut_panics() {
+        let shared = Shared::new(1);
+        let _value = shared.borrow();
+        *shared.borrow_mut() += 1;
+    }
+
+    #[test]
+    #[should_panic(expected = "already mutably borrowed")]
+    fn borrow_while_mutable_panics() {
+        let shared = Shared::new(1);
+        let _value = shared.borrow_mut();
+        let _ = *shared.borrow();
+    }
+
+    #[test]
+    fn one_thread_at_a_time() {
+        let shared = Shared::new(1);
+        let other = shared.clone();
+        // handed over, the other thread owns the value until it is joined
+        std::thread::spawn(move || *other.borrow_mut() += 1).join().unwrap();
+        assert_eq!(*shared.borrow(), 2);
+        let other = shared.clone();
+        let _value = shared.borrow_mut();
+        assert!(std::thread::spawn(move || *other.borrow()).join().is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::moves_list::MoveItem;
+
+/// Entries of the table a game starts with, about 5 MB.
+pub const DEFAULT_TT_SIZE: usize = 1 << 16;
+
+// mixed into the keys of the positions scored by `state_only` evaluation
+const STATE_ONLY_KEY: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// What the stored score says about the position's value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// the value is at least the score, the search was cut off
+    Lower,
+    /// the value is at most the score, no move reached the window
+    Upper,
+}
+
+#[derive(Clone, Debug)]
+pub struct TtEntry {
+    pub key: u64,
+    /// plies searched below the position
+    pub depth: i16,
+    pub bound: Bound,
+    pub score: i32,
+    pub mov: Option<MoveItem>,
+    age: u8,
+}
+
+/// Fixed-size table of searched positions keyed by `Position::hash` and the kind of evaluation, one entry a slot.
+/// An entry of an earlier search is always replaced, one of the current search only by a deeper one.
+#[derive(Clone, Debug)]
+pub struct TranspositionTable {
+    size: usize,
+    // allocated on the first store
+    entries: Vec<Option<TtEntry>>,
+    age: u8,
+}
+
+impl TranspositionTable {
+    /// Size is rounded down to a power of two, 0 turns the table off.
+    pub fn new(size: usize) -> TranspositionTable {
+        let size = if size == 0 { 0 } else { 1 << size.ilog2() };
+        TranspositionTable { size, entries: vec![], age: 0 }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.size > 0
+    }
+
+    pub fn clear(&mut self) {
+        self.entries = vec![];
+    }
+
+    /// Called at the start of every search, the entries stored before become old.
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    /// Searches started so far, wrapping around.
+    pub fn age(&self) -> u8 {
+        self.age
+    }
+
+    /// Key of the position scored with or without the placement of the pieces, see `Position::evaluate`.
+    pub fn key(hash: u64, state_only: bool) -> u64 {
+        if state_only { hash ^ STATE_ONLY_KEY } else { hash }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.size - 1)
+    }
+
+    pub fn probe(&self, key: u64) -> Option<&TtEntry> {
+        if self.entries.is_empty() { return None; }
+        self.entries[self.index(key)].as_ref().filter(|entry| entry.key == key)
+    }
+
+    pub fn store(&mut self, key: u64, depth: i16, bound: Bound, score: i32, mov: Option<MoveItem>) {
+        if !self.is_enabled() { return; }
+        if self.entries.is_empty() { self.entries = vec![None; self.size]; }
+        let (age, index) = (self.age, self.index(key));
+        let slot = &mut self.entries[index];
+        if slot.as_ref().is_some_and(|entry| entry.age == age && entry.depth > depth) { return; }
+        // a shallower search of the same position keeps the move found before
+        let mov = mov.or_else(|| slot.as_ref().filter(|entry| entry.key == key).and_then(|entry| entry.mov.clone()));
+        *slot = Some(TtEntry { key, depth, bound, score, mov, age });
+    }
+
+    /// Bound of the score searched in the window from `alpha` to `beta`, White's advantage positive.
+    pub fn bound(score: i32, alpha: i32, beta: i32) -> Bound {
+        if score >= beta { Bound::Lower } else if score <= alpha { Bound::Upper } else { Bound::Exact }
+    }
+}
+
+impl TtEntry {
+    /// The score decides the search at `depth` in the window from `alpha` to `beta`.
+    pub fn cuts(&self, depth: i16, alpha: i32, beta: i32) -> bool {
+        self.depth >= depth && match self.bound {
+            Bound::Exact => true,
+            Bound::Lower => self.score >= beta,
+            Bound::Upper => self.score <= alpha,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Game;
+    use crate::rules::Variant;
+    use crate::transposition::{Bound, TranspositionTable};
+
+    #[test]
+    fn replacement() {
+        let mut tt = TranspositionTable::new(100);
+        assert_eq!(tt.size(), 64);
+        assert!(tt.probe(5).is_none());
+        tt.store(5, 3, Bound::Exact, 10, None);
+        // the same slot: a shallower entry of the same search does not replace it
+        tt.store(5 + 64, 2, Bound::Lower, 20, None);
+        assert_eq!(tt.probe(5).unwrap().score, 10);
+        assert!(tt.probe(5 + 64).is_none());
+        tt.new_search();
+        tt.store(5 + 64, 2, Bound::Lower, 20, None);
+        assert!(tt.probe(5).is_none());
+        assert_eq!(tt.probe(5 + 64).unwrap().bound, Bound::Lower);
+        assert!(!TranspositionTable::new(0).is_enabled());
+    }
+
+    // scores do not depend on the table; state_only leaves out the random part of the evaluation
+    fn same_scores(variant: Variant, fen: Option<&str>, max_depth: i16) -> (Game, Game) {
+        let mut with_tt = Game::new_standard(variant);
+        let mut without_tt = Game::new_standard(variant);
+        if let Some(fen) = fen {
+            with_tt.set_fen(fen).unwrap();
+            without_tt.set_fen(fen).unwrap();
+        }
+        without_tt.set_tt_size(0);
+        for depth in 1..=max_depth {
+            let a = with_tt.best_move(depth, i32::MIN / 2, i32::MAX / 2, 0, true);
+            let b = without_tt.best_move(depth, i32::MIN / 2, i32::MAX / 2, 0, true);
+            assert_eq!(a.deep_eval, b.deep_eval, "{:?} depth {}", variant, depth);
+        }
+        assert!(with_tt.tt.probe(TranspositionTable::key(with_tt.current_position.hash(), true)).is_some());
+        (with_tt, without_tt)
+    }
+
+    #[test]
+    fn same_as_without_table() {
+        for (variant, max_depth) in [(Variant::Russian, 5), (Variant::International, 4), (Variant::English, 5)] {
+            let (with_tt, without_tt) = same_scores(variant, None, max_depth);
+            // the men reach the same squares in another order, the table cuts off there
+            assert!(with_tt.search.tt_cutoffs > 0);
+            assert!(with_tt.search.nodes < without_tt.search.nodes, "{} {}", with_tt.search.nodes, without_tt.search.nodes);
+        }
+        // kings only: below the root the draw rules count the moves, the table is not used there
+        let (with_tt, _) = same_scores(Variant::Russian, Some("W:WK1,K22:BK10,K32"), 6);
+        assert_eq!(with_tt.search.tt_cutoffs, 0);
+    }
+
+    #[test]
+    fn keyed_by_evaluation() {
+        let mut game = Game::new_standard(Variant::Russian);
+        game.best_move(3, i32::MIN / 2, i32::MAX / 2, 0, false);
+        let hash = game.current_position.hash();
+        assert!(game.tt.probe(TranspositionTable::key(hash, false)).is_some());
+        assert!(game.tt.probe(TranspositionTable::key(hash, true)).is_none());
+    }
+
+    #[test]
+    fn repetition_through_the_table() {
+        let [mut with_tt, mut without_tt] = [0, 1].map(|_| {
+            let mut game = Game::new_variant(Variant::International);
+            game.set_fen("W:WK47,K48:BK3,16").unwrap();
+            // the kings go and come back, the position is on the board the second time
+            for mov in ["47-41", "3-9", "41-47", "9-3"] {
+                game.make_move_by_notation(mov).unwrap();
+            }
+            game
+        });
+        without_tt.set_tt_size(0);
+        // after 47-41 the same moves lead to the third repetition, a draw; the score stored
+        // for that position by a search without this history knows nothing of it
+        let mut other = Game::new_variant(Variant::International);
+        other.set_fen("B:WK41,K48:BK3,16").unwrap();
+        let key = TranspositionTable::key(other.current_position.hash(), true);
+        with_tt.tt.store(key, 8, Bound::Exact, 1_000_000, None);
+        for depth in 1..=4 {
+            let a = with_tt.best_move(depth, i32::MIN / 2, i32::MAX / 2, 0, true);
+            let b = without_tt.best_move(depth, i32::MIN / 2, i32::MAX / 2, 0, true);
+            assert_eq!(a.deep_eval, b.deep_eval, "depth {}", depth);
+        }
+    }
+}
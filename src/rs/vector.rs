@@ -8,7 +8,7 @@ use ts_rs::TS;
 pub struct Vector<T> {
     pub(crate) points: Arc<Vec<T>>,
     pub(crate) direction: i8,
-    // 0..3 (0 - UR, 1 - UL, 2 - DL, 3 - DR): used in Game
+    // 0..3 (0 - UR, 1 - UL, 2 - DL, 3 - DR), 4..7 (4 - U, 5 - L, 6 - D, 7 - R): used in Game
     range_a: Option<usize>,
     range_b: Option<usize>,
 }
@@ -30,7 +30,7 @@ impl<T> Vector<T> {
     }
 
     pub fn get_ban_direction(&self) -> i8 {
-        (self.direction + 2) % 4
+        self.direction / 4 * 4 + (self.direction + 2) % 4
     }
     pub fn set_range(&mut self, a: usize, b: usize) {
         self.range_a = Some(a);
@@ -0,0 +1,185 @@
+use crate::color::Color;
+use crate::moves::BoardPos;
+use crate::piece::Piece;
+
+/// Squares of the largest board, 12x12.
+pub(crate) const MAX_CELLS: usize = 144;
+
+const fn split_mix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+/// King move counters the hash tells apart, the larger ones share the key of the last.
+pub(crate) const KING_MOVE_COUNTS: usize = 4;
+
+type Keys = ([[u64; 4]; MAX_CELLS], u64, [[u64; KING_MOVE_COUNTS]; 2]);
+
+// fixed seed: the keys are stored in the MCTS cache and must not change between runs
+const fn keys() -> Keys {
+    let mut pieces = [[0; 4]; MAX_CELLS];
+    let mut state = 0x636B_6572_735F_7A62;
+    let mut i = 0;
+    while i < MAX_CELLS {
+        let mut kind = 0;
+        while kind < 4 {
+            let (next, key) = split_mix(state);
+            state = next;
+            pieces[i][kind] = key;
+            kind += 1;
+        }
+        i += 1;
+    }
+    let (mut state, black_to_move) = split_mix(state);
+    // a zero counter adds nothing, the hash of the other variants stays as it was
+    let mut king_moves = [[0; KING_MOVE_COUNTS]; 2];
+    let mut color = 0;
+    while color < 2 {
+        let mut count = 1;
+        while count < KING_MOVE_COUNTS {
+            let (next, key) = split_mix(state);
+            state = next;
+            king_moves[color][count] = key;
+            count += 1;
+        }
+        color += 1;
+    }
+    (pieces, black_to_move, king_moves)
+}
+
+const KEYS: Keys = keys();
+
+/// Key of the piece on the packed square.
+pub fn piece_key(piece: &Piece, pos: BoardPos) -> u64 {
+    let kind = if piece.color == Color::White { 0 } else { 2 } + piece.is_king as usize;
+    KEYS.0[pos][kind]
+}
+
+/// Added to the hash when Black is to move.
+pub fn black_to_move_key() -> u64 {
+    KEYS.1
+}
+
+/// Added to the hash for the quiet king moves the side has made in a row.
+pub fn king_moves_key(color: Color, count: u8) -> u64 {
+    KEYS.2[color as usize][(count as usize).min(KING_MOVE_COUNTS - 1)]
+}
+
+/// Hash of the cells and the side to move, computed from scratch.
+pub fn hash_cells(cells: &[Option<Piece>], next_move: Option<Color>) -> u64 {
+    let pieces = cells.iter().enumerate()
+        .filter_map(|(pos, cell)| cell.as_ref().map(|piece| piece_key(piece, pos)))
+        .fold(0, |hash, key| hash ^ key);
+    if next_move == Some(Color::Black) { pieces ^ black_to_move_key() } else { pieces }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use crate::color::Color;
+    use crate::game::Game;
+    use crate::mcts::McTree;
+    use crate::piece::Piece;
+    use crate::position::Position;
+    use crate::PositionHistory::PositionHistory;
+    use crate::rules::Variant;
+    use crate::shared::Shared;
+    use crate::zobrist::{hash_cells, king_moves_key};
+
+    fn from_scratch(pos: &Position) -> u64 {
+        let limit = pos.environment.rules.king_moves_limit();
+        let king_moves = if limit == 0 { 0 } else {
+            king_moves_key(Color::White, pos.state.white_king_moves.min(limit))
+                ^ king_moves_key(Color::Black, pos.state.black_king_moves.min(limit))
+        };
+        hash_cells(&pos.cells, pos.next_move) ^ king_moves
+    }
+
+    #[test]
+    fn incremental_hash() {
+        let mut rng = StdRng::seed_from_u64(16);
+        for variant in [Variant::Russian, Variant::International, Variant::English, Variant::Italian,
+            Variant::Turkish, Variant::Frisian, Variant::Canadian] {
+            for _ in 0..10 {
+                let mut pos = Game::new_standard(variant).current_position;
+                let start = pos.hash();
+                assert_eq!(start, from_scratch(&pos));
+                let mut played = vec![];
+                for _ in 0..150 {
+                    let list = pos.get_move_list(false);
+                    // generating the captures moves pieces around and back
+                    assert_eq!(pos.hash(), from_scratch(&pos), "{:?}", variant);
+                    if list.list.is_empty() { break; }
+                    let mov = list.list[rng.gen_range(0..list.list.len())].clone();
+                    pos.make_move(&mov);
+                    assert_eq!(pos.hash(), from_scratch(&pos), "{:?} {:?}", variant, mov);
+                    played.push((mov, pos.took_pieces.clone()));
+                }
+                while let Some((mov, took_pieces)) = played.pop() {
+                    pos.took_pieces = took_pieces;
+                    pos.unmake_move(&mov);
+                    assert_eq!(pos.hash(), from_scratch(&pos), "{:?} {:?}", variant, mov);
+                }
+                assert_eq!(pos.hash(), start);
+            }
+        }
+    }
+
+    #[test]
+    fn frisian_king_moves() {
+        // the kings go there and back, the pieces are the same but the counters are not
+        let hashes = |variant| {
+            let mut game = Game::new_variant(variant);
+            game.current_position.next_move = Some(Color::White);
+            game.insert_piece(Piece::new(game.to_pack(0), Color::White, true));
+            game.insert_piece(Piece::new(game.to_pack(6), Color::White, false));
+            game.insert_piece(Piece::new(game.to_pack(95), Color::Black, true));
+            let mut hashes = vec![game.current_position.hash()];
+            for (from, to) in [(0, 11), (95, 86), (11, 0), (86, 95)] {
+                let (from, to) = (game.to_pack(from), game.to_pack(to));
+                let pos = &mut game.current_position;
+                let list = pos.get_move_list(false);
+                pos.make_move(list.list.iter().find(|x| x.from() == from && x.to() == to).unwrap());
+                assert_eq!(pos.hash(), from_scratch(pos), "{:?}", variant);
+                hashes.push(pos.hash());
+            }
+            hashes
+        };
+        let frisian = hashes(Variant::Frisian);
+        assert_ne!(frisian[0], frisian[4]);
+        let international = hashes(Variant::International);
+        assert_eq!(international[0], international[4]);
+    }
+
+    #[test]
+    fn side_and_pieces() {
+        let mut game = Game::new(8);
+        let empty = game.current_position.hash();
+        game.current_position.next_move = Some(Color::Black);
+        assert_ne!(game.current_position.hash(), empty);
+        game.current_position.next_move = Some(Color::White);
+        game.insert_piece(Piece::new(5, Color::White, false));
+        let man = game.current_position.hash();
+        game.remove_piece(5);
+        game.insert_piece(Piece::new(5, Color::White, true));
+        assert_ne!(game.current_position.hash(), man);
+        assert_eq!(game.current_position.hash(), from_scratch(&game.current_position));
+        game.remove_piece(5);
+        assert_eq!(game.current_position.hash(), empty);
+    }
+
+    #[test]
+    fn tree_child_keys() {
+        let pos = Game::new_standard(Variant::Russian).current_position;
+        let tree = McTree::new(pos, Shared::new(PositionHistory::new()));
+        tree.root.borrow_mut().expand();
+        let root = tree.root.borrow();
+        let childs = &root.childs;
+        assert_eq!(childs.len(), 7);
+        assert!(childs.iter().all(|(key, x)| *key == from_scratch(&x.borrow().pos_mov.borrow().pos)));
+    }
+}